@@ -3,8 +3,9 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use deposit_cw20::msg::{Cw20DepositResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use deposit_cw20::state::{Cw20Deposits};
+use deposit_cw20::msg::{
+    BalancesResponse, ConfigResponse, Cw20DepositResponse, Cw20Deposits, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -15,6 +16,9 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(Cw20Deposits), &out_dir);
     export_schema(&schema_for!(Cw20DepositResponse), &out_dir);
+    export_schema(&schema_for!(BalancesResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
 }
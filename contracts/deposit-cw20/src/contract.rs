@@ -1,30 +1,65 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, from_binary, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Uint128, WasmMsg, BankMsg, coin
+    to_binary, from_binary, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Order, OverflowError, OverflowOperation, BlockInfo, Response, StdError, StdResult, Storage, Uint128, WasmMsg, BankMsg, Coin, Decimal, Decimal256, Fraction, coin
 };
 
-use cw2::set_contract_version;
-use cw20::{Cw20ReceiveMsg, Expiration};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::Bound;
+use cw20::Cw20ReceiveMsg;
 use cw20_base;
-// use cw2::set_contract_version;
+use cw_utils::{maybe_addr, Duration, Expiration};
+use semver::Version;
 
 use crate::error::ContractError;
-use crate::msg::{Cw20DepositResponse, ExecuteMsg, InstantiateMsg, QueryMsg, Cw20HookMsg, DepositResponse};
-use crate::state::{Cw20Deposits, CW20_DEPOSITS, DEPOSITS, Deposits};
+use crate::migrations;
+use crate::rewards;
+use crate::denoms;
+use crate::msg::{Cw20DepositResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, Cw20HookMsg, DepositResponse, ConfigResponse, LockDurationResponse, Cw20BalanceResponse, Cw20LotsResponse, AllowedToken, AllowedTokensResponse, AllowedDenom, AllowedDenomsResponse, DepositorsResponse, DenomTotalResponse, AllDenomTotalsResponse, Cw20TotalResponse, Holder, HoldersResponse, AssetInfo, AllowanceResponse, AllowanceKey, AllowanceInfo, AllAllowancesResponse, PendingReward, PendingRewardsResponse, ClaimsResponse, LockTierInfo, LockTiersResponse, DenomInfoResponse, Deposits, Cw20Deposits, BalancesResponse, ReceiveMsg, Cw1155BatchReceiveMsg, Cw1155HookMsg, Cw1155ExecuteMsg, Cw1155BalanceResponse, Cw1155LotsResponse, AllowedCw1155sResponse};
+use crate::state::{BlockStamp, Balance, balances, balance_key, NATIVE_KIND, CW20_KIND, Config, CONFIG, CW20_LOCK_DURATIONS, Cw20Lot, CW20_LOTS, next_lot_id, Operation, PauseState, PAUSED, TokenLimits, ALLOWED_CW20S, DenomLimits, ALLOWED_DENOMS, DENOM_TOTALS, CW20_TOTALS, CW20_UNBONDING_TOTALS, DEPOSITORS, DepositAllowance, ALLOWANCES, asset_key, RewardPool, REWARD_POOLS, USER_REWARDS, USER_PENALTIES, UNCLAIMED_PENALTIES, EarlyWithdrawPolicy, PenaltyDestination, Cw20Claim, CW20_CLAIMS, next_claim_id, LockTier, LOCK_TIERS, CW20_WEIGHTS, CW20_WEIGHTED_TOTALS, DenomKind, DENOM_KINDS, IbcTrace, IBC_TRACES, Cw1155Lot, CW1155_LOTS, CW1155_BALANCES, ALLOWED_CW1155S};
 
 const CONTRACT_NAME: &str = "deposit-cw20-example";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+// longest memo stored on a lot
+const MAX_MEMO_LENGTH: usize = 256;
+
+// largest reward multiplier of a lock tier
+const MAX_REWARD_MULTIPLIER: u64 = 100;
+
+// most lots an owner can hold in one asset, so withdraws stay within gas
+const MAX_LOTS: usize = 50;
+// most lots deposits of others can take an owner to, the rest is left to the owner
+const MAX_LOTS_FROM_OTHERS: usize = 40;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
+    info: MessageInfo,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::default())
+    let admin = match msg.admin {
+        Some(admin) => deps.api.addr_validate(&admin)?,
+        None => info.sender,
+    };
+    let config = Config {
+        admin,
+        pending_admin: None,
+        lock_duration: msg.lock_duration,
+        early_withdraw: None,
+        unbonding_period: None,
+    };
+    CONFIG.save(deps.storage, &config)?;
+    PAUSED.save(deps.storage, &PauseState::default())?;
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("admin", config.admin))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -35,20 +70,230 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Deposit { } => execute_deposit(deps, env,  info),
-        ExecuteMsg::Withdraw { amount, denom } => execute_withdraw(deps, info, amount, denom),
-        ExecuteMsg::Receive(cw20_msg) => receive_cw20(deps, env, info, cw20_msg),
-        ExecuteMsg::WithdrawCw20 { address, amount } => execute_cw20_withdraw(deps, env, info, address, amount),
+        ExecuteMsg::Deposit { } => {
+            assert_not_paused(deps.as_ref(), Operation::NativeDeposit)?;
+            let owner = info.sender.clone();
+            execute_deposit(deps, env, info, owner, None)
+        },
+        ExecuteMsg::DepositFor { beneficiary, memo } => {
+            assert_not_paused(deps.as_ref(), Operation::NativeDeposit)?;
+            let owner = deps.api.addr_validate(&beneficiary).map_err(|_| ContractError::InvalidOwner {})?;
+            execute_deposit(deps, env, info, owner, memo)
+        },
+        ExecuteMsg::Withdraw { asset, denom, amount, recipient } => match withdrawn_asset(asset, denom)? {
+            AssetInfo::Native { denom } => {
+                assert_not_paused(deps.as_ref(), Operation::NativeWithdraw)?;
+                execute_withdraw(deps, env, info, amount, denom, recipient)
+            },
+            AssetInfo::Cw20 { contract } => {
+                assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+                execute_cw20_withdraw(deps, env, info, contract, amount, recipient)
+            },
+        },
+        ExecuteMsg::WithdrawAll { denom } => {
+            assert_not_paused(deps.as_ref(), Operation::NativeWithdraw)?;
+            execute_withdraw_all(deps, env, info, denom)
+        },
+        ExecuteMsg::Receive(ReceiveMsg::Cw20(cw20_msg)) => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Deposit)?;
+            receive_cw20(deps, env, info, cw20_msg)
+        },
+        ExecuteMsg::Receive(ReceiveMsg::Cw1155(cw1155_msg)) => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Deposit)?;
+            let batch = vec![(cw1155_msg.token_id, cw1155_msg.amount)];
+            execute_cw1155_deposit(deps, env, info, cw1155_msg.operator, cw1155_msg.from, batch, cw1155_msg.msg)
+        },
+        ExecuteMsg::BatchReceive(batch_msg) => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Deposit)?;
+            let Cw1155BatchReceiveMsg { operator, from, batch, msg } = batch_msg;
+            execute_cw1155_deposit(deps, env, info, operator, from, batch, msg)
+        },
+        ExecuteMsg::WithdrawCw1155 { contract, token_id, amount, recipient } => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_cw1155_withdraw(deps, env, info, contract, token_id, amount, recipient)
+        },
+        ExecuteMsg::WithdrawCw20 { address, amount, recipient } => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_cw20_withdraw(deps, env, info, address, amount, recipient)
+        },
+        ExecuteMsg::EarlyWithdrawCw20 { contract, amount } => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_early_withdraw_cw20(deps, env, info, contract, amount)
+        },
+        ExecuteMsg::SetEarlyWithdrawPolicy { policy } => execute_set_early_withdraw_policy(deps, info, policy),
+        ExecuteMsg::UnbondCw20 { contract, amount } => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_unbond_cw20(deps, env, info, contract, amount)
+        },
+        ExecuteMsg::ClaimCw20 {} => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_claim_cw20(deps, env, info)
+        },
+        ExecuteMsg::SetUnbondingPeriod { period } => execute_set_unbonding_period(deps, info, period),
+        ExecuteMsg::WithdrawAllCw20 { contract } => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_withdraw_all_cw20(deps, env, info, contract)
+        },
+        ExecuteMsg::TransferDeposit { recipient, denom, amount } => {
+            assert_not_paused(deps.as_ref(), Operation::NativeWithdraw)?;
+            execute_transfer_deposit(deps, env, info, recipient, denom, amount)
+        },
+        ExecuteMsg::TransferCw20Deposit { recipient, contract, amount } => {
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_transfer_cw20_deposit(deps, env, info, recipient, contract, amount)
+        },
+        ExecuteMsg::IncreaseDepositAllowance { spender, asset, amount, expires } => {
+            execute_increase_allowance(deps, env, info, spender, asset, amount, expires)
+        },
+        ExecuteMsg::DecreaseDepositAllowance { spender, asset, amount, expires } => {
+            execute_decrease_allowance(deps, env, info, spender, asset, amount, expires)
+        },
+        ExecuteMsg::WithdrawFrom { owner, asset, amount, recipient } => {
+            let operation = match asset {
+                AssetInfo::Native { .. } => Operation::NativeWithdraw,
+                AssetInfo::Cw20 { .. } => Operation::Cw20Withdraw,
+            };
+            assert_not_paused(deps.as_ref(), operation)?;
+            execute_withdraw_from(deps, env, info, owner, asset, amount, recipient)
+        },
+        ExecuteMsg::SetRewardPool { contract, reward, emission_per_second } => {
+            execute_set_reward_pool(deps, env, info, contract, reward, emission_per_second)
+        },
+        ExecuteMsg::FundRewards { contract } => {
+            let coins = match info.funds.as_slice() {
+                [coins] => coins.clone(),
+                [] => return Err(ContractError::NoFunds {}),
+                _ => return Err(ContractError::InvalidCoin {}),
+            };
+            let reward = AssetInfo::Native { denom: coins.denom };
+            execute_fund_rewards(deps, env, info.sender, contract, reward, coins.amount)
+        },
+        ExecuteMsg::ClaimRewards {} => execute_claim_rewards(deps, env, info),
+        ExecuteMsg::ReweightCw20 { address, contract } => execute_reweight_cw20(deps, env, address, contract),
+        ExecuteMsg::WithdrawEverything {} => {
+            assert_not_paused(deps.as_ref(), Operation::NativeWithdraw)?;
+            assert_not_paused(deps.as_ref(), Operation::Cw20Withdraw)?;
+            execute_withdraw_everything(deps, env, info)
+        },
+        ExecuteMsg::UpdateConfig { lock_duration } => execute_update_config(deps, info, lock_duration),
+        ExecuteMsg::SetCw20LockDuration { contract, lock_duration } => execute_set_cw20_lock_duration(deps, info, contract, lock_duration),
+        ExecuteMsg::SetLockTier { name, duration, reward_multiplier } => execute_set_lock_tier(deps, info, name, duration, reward_multiplier),
+        ExecuteMsg::RemoveLockTier { name } => execute_remove_lock_tier(deps, info, name),
+        ExecuteMsg::ProposeNewAdmin { admin } => execute_propose_new_admin(deps, info, admin),
+        ExecuteMsg::AcceptAdmin {} => execute_accept_admin(deps, info),
+        ExecuteMsg::AddAllowedToken { contract, min_deposit, max_deposit } => {
+            execute_add_allowed_token(deps, info, contract, min_deposit, max_deposit)
+        },
+        ExecuteMsg::RemoveAllowedToken { contract } => execute_remove_allowed_token(deps, info, contract),
+        ExecuteMsg::AddAllowedCw1155 { contract } => execute_add_allowed_cw1155(deps, info, contract),
+        ExecuteMsg::RemoveAllowedCw1155 { contract } => execute_remove_allowed_cw1155(deps, info, contract),
+        ExecuteMsg::AddAllowedDenom { denom, min_deposit, max_user_balance, global_cap } => {
+            execute_add_allowed_denom(deps, info, denom, DenomLimits { min_deposit, max_user_balance, global_cap })
+        },
+        ExecuteMsg::RemoveAllowedDenom { denom } => execute_remove_allowed_denom(deps, info, denom),
+        ExecuteMsg::SetIbcTrace { denom, trace } => execute_set_ibc_trace(deps, info, denom, trace),
+        ExecuteMsg::SetPause { native_deposit, native_withdraw, cw20_deposit, cw20_withdraw } => {
+            execute_set_pause(deps, info, native_deposit, native_withdraw, cw20_deposit, cw20_withdraw)
+        },
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::WrongContract { expected: CONTRACT_NAME.to_string(), found: stored.contract });
+    }
+
+    let previous = parse_version(&stored.version)?;
+    let current = parse_version(CONTRACT_VERSION)?;
+    if previous > current {
+        return Err(ContractError::CannotDowngrade { previous: stored.version, current: CONTRACT_VERSION.to_string() });
+    }
+
+    //each step upgrades the storage layout of one release to the next one
+    if previous < Version::new(0, 2, 0) {
+        migrations::v0_2::migrate(deps.branch(), &env, msg.admin)?;
+    }
+    if previous < Version::new(0, 3, 0) {
+        migrations::v0_3::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 4, 0) {
+        migrations::v0_4::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 5, 0) {
+        migrations::v0_5::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 6, 0) {
+        migrations::v0_6::migrate(deps.branch(), &env)?;
+    }
+    if previous < Version::new(0, 7, 0) {
+        migrations::v0_7::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 8, 0) {
+        migrations::v0_8::migrate(deps.branch())?;
     }
+    if previous < Version::new(0, 9, 0) {
+        migrations::v0_9::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 10, 0) {
+        migrations::v0_10::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 11, 0) {
+        migrations::v0_11::migrate(deps.branch())?;
+    }
+    if previous < Version::new(0, 12, 0) {
+        migrations::v0_12::migrate(deps.branch())?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("previous_version", stored.version)
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
+fn parse_version(version: &str) -> StdResult<Version> {
+    version.parse().map_err(|_| StdError::generic_err(format!("Invalid version {}", version)))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::Deposits { address } => {
-            to_binary(&query_deposits(deps, address)?)
+        QueryMsg::Balances { address, start_after, limit } => to_binary(&query_balances(deps, address, start_after, limit)?),
+        QueryMsg::Deposits { address, start_after, limit } => {
+            to_binary(&query_deposits(deps, address, start_after, limit)?)
+        },
+        QueryMsg::Cw20Deposits { address, start_after, limit } => to_binary(&query_cw20_deposits(deps, address, start_after, limit)?),
+        QueryMsg::Cw20Balance { address, contract } => to_binary(&query_cw20_balance(deps, env, address, contract)?),
+        QueryMsg::Cw20Lots { address, contract, start_after, limit } => {
+            to_binary(&query_cw20_lots(deps, address, contract, start_after, limit)?)
+        },
+        QueryMsg::Cw1155Balance { address, contract, token_id } => {
+            to_binary(&query_cw1155_balance(deps, env, address, contract, token_id)?)
+        },
+        QueryMsg::Cw1155Lots { address, contract, token_id, start_after, limit } => {
+            to_binary(&query_cw1155_lots(deps, address, contract, token_id, start_after, limit)?)
         },
-        QueryMsg::Cw20Deposits { address } => to_binary(&query_cw20_deposits(deps, address)?),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::PauseStatus {} => to_binary(&query_pause_status(deps)?),
+        QueryMsg::AllDepositors { start_after, limit } => to_binary(&query_all_depositors(deps, start_after, limit)?),
+        QueryMsg::DenomTotal { denom } => to_binary(&query_denom_total(deps, denom)?),
+        QueryMsg::Cw20Total { contract } => to_binary(&query_cw20_total(deps, contract)?),
+        QueryMsg::AllDenomTotals { start_after, limit } => to_binary(&query_all_denom_totals(deps, start_after, limit)?),
+        QueryMsg::DenomHolders { denom, start_after, limit } => to_binary(&query_denom_holders(deps, denom, start_after, limit)?),
+        QueryMsg::Cw20Holders { contract, start_after, limit } => to_binary(&query_cw20_holders(deps, contract, start_after, limit)?),
+        QueryMsg::AllowedTokens { start_after, limit } => to_binary(&query_allowed_tokens(deps, start_after, limit)?),
+        QueryMsg::AllowedCw1155s { start_after, limit } => to_binary(&query_allowed_cw1155s(deps, start_after, limit)?),
+        QueryMsg::AllowedDenoms { start_after, limit } => to_binary(&query_allowed_denoms(deps, start_after, limit)?),
+        QueryMsg::DenomInfo { denom } => to_binary(&query_denom_info(deps, denom)?),
+        QueryMsg::Allowance { owner, spender, asset } => to_binary(&query_allowance(deps, owner, spender, asset)?),
+        QueryMsg::AllAllowances { owner, start_after, limit } => to_binary(&query_all_allowances(deps, owner, start_after, limit)?),
+        QueryMsg::RewardPool { contract } => to_binary(&query_reward_pool(deps, env, contract)?),
+        QueryMsg::PendingRewards { address } => to_binary(&query_pending_rewards(deps, env, address)?),
+        QueryMsg::Claims { address, start_after, limit } => to_binary(&query_claims(deps, address, start_after, limit)?),
+        QueryMsg::LockTiers { start_after, limit } => to_binary(&query_lock_tiers(deps, start_after, limit)?),
+        QueryMsg::EffectiveLockDuration { contract } => to_binary(&query_effective_lock_duration(deps, contract)?),
     }
 }
 
@@ -59,7 +304,18 @@ pub fn receive_cw20(
     cw20_msg: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
     match from_binary(&cw20_msg.msg) {
-        Ok(Cw20HookMsg::Deposit { }) => execute_cw20_deposit(deps, env, info, cw20_msg.sender, cw20_msg.amount),
+        Ok(Cw20HookMsg::Deposit { lock_tier }) => {
+            let owner = cw20_msg.sender.clone();
+            execute_cw20_deposit(deps, env, info, cw20_msg, owner, lock_tier, None)
+        },
+        Ok(Cw20HookMsg::DepositFor { beneficiary, memo, lock_tier }) => {
+            execute_cw20_deposit(deps, env, info, cw20_msg, beneficiary, lock_tier, memo)
+        },
+        Ok(Cw20HookMsg::FundRewards { contract }) => {
+            let funder = deps.api.addr_validate(&cw20_msg.sender)?;
+            let reward = AssetInfo::Cw20 { contract: info.sender.into_string() };
+            execute_fund_rewards(deps, env, funder, contract, reward, cw20_msg.amount)
+        },
         _ => Err(ContractError::CustomError { val: "Invalid Cw20HookMsg".to_string() }),
     }
 }
@@ -68,101 +324,354 @@ pub fn execute_deposit(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    owner: Addr,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
-    let sender = info.sender.clone().into_string();
+    if info.funds.is_empty() {
+        return Err(ContractError::NoFunds {});
+    }
+    validate_memo(memo.as_deref())?;
 
-    let d_coins = info.funds[0].clone();
-    
-    //check to see if deposit exists
-    match DEPOSITS.load(deps.storage, (&sender, d_coins.denom.as_str())) {
-        Ok(mut deposit) => {
-            //add coins to their account
-            // deposit.coins.amount += d_coins.amount;
-            deposit.coins.amount = deposit.coins.amount.checked_add(d_coins.amount).unwrap();
-            deposit.count = deposit.count.checked_add(1).unwrap();
-            DEPOSITS.save(deps.storage, (&sender, d_coins.denom.as_str()), &deposit).unwrap();
+    DEPOSITORS.save(deps.storage, &owner, &Empty {})?;
+
+    let mut res = Response::new().add_attribute("execute", "deposit");
+    if owner != info.sender {
+        res = res
+            .add_attribute("owner", owner.as_str())
+            .add_attribute("depositor", info.sender.as_str());
+    }
+    if let Some(memo) = memo {
+        res = res.add_attribute("memo", memo);
+    }
+    for d_coins in info.funds {
+        if d_coins.amount.is_zero() {
+            return Err(ContractError::ZeroAmount { denom: d_coins.denom });
         }
-        Err(_) => {
-            //user does not exist, add them.
-            let deposit = Deposits {
-                count: 1,
-                owner: info.sender,
-                coins: d_coins.clone(),
-            };
-            DEPOSITS.save(deps.storage, (&sender, d_coins.denom.as_str()), &deposit).unwrap();
+
+        let limits = ALLOWED_DENOMS
+            .may_load(deps.storage, &d_coins.denom)?
+            .ok_or_else(|| ContractError::DenomNotAllowed { denom: d_coins.denom.clone() })?;
+        if let Some(min) = limits.min_deposit {
+            if d_coins.amount < min {
+                return Err(ContractError::DepositTooSmall { asset: d_coins.denom, min });
+            }
+        }
+
+        let total = DENOM_TOTALS
+            .may_load(deps.storage, &d_coins.denom)?
+            .unwrap_or_default()
+            .checked_add(d_coins.amount)?;
+        if let Some(cap) = limits.global_cap {
+            if total > cap {
+                return Err(ContractError::GlobalCapExceeded { denom: d_coins.denom, cap });
+            }
         }
+        DENOM_TOTALS.save(deps.storage, &d_coins.denom, &total)?;
+
+        credit_native(deps.storage, &env.block, &owner, &d_coins, Some(&limits))?;
+        res = res
+            .add_attribute("denom", d_coins.denom)
+            .add_attribute("amount", d_coins.amount);
+    }
+    Ok(res)
+}
+
+// adds `coins` to the native deposit of an owner, creating the record when needed
+fn credit_native(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    coins: &Coin,
+    limits: Option<&DenomLimits>,
+) -> Result<(), ContractError> {
+    let asset = AssetInfo::Native { denom: coins.denom.clone() };
+    let balance = credit_balance(storage, block, owner, &asset, coins.amount)?;
+    if let Some(limits) = limits {
+        assert_user_balance_cap(limits, &coin(balance.amount.u128(), &coins.denom))?;
+    }
+    Ok(())
+}
+
+fn assert_user_balance_cap(limits: &DenomLimits, balance: &Coin) -> Result<(), ContractError> {
+    match limits.max_user_balance {
+        Some(cap) if balance.amount > cap => Err(ContractError::UserBalanceCapExceeded { denom: balance.denom.clone(), cap }),
+        _ => Ok(()),
     }
-    Ok(Response::new()
-        .add_attribute("execute", "deposit")
-        .add_attribute("denom", d_coins.denom)
-        .add_attribute("amount", d_coins.amount)
-    )
 }
 
 pub fn execute_withdraw(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    amount:u128,
-    denom:String
+    amount: Uint128,
+    denom:String,
+    recipient: Option<String>,
 ) -> Result<Response, ContractError> {
+    let recipient = maybe_addr(deps.api, recipient)?.unwrap_or_else(|| info.sender.clone());
 
-    let sender = info.sender.clone().into_string();
+    if amount.is_zero() || denom.is_empty() {
+        return Err(ContractError::InvalidCoin {});
+    }
 
-    let mut deposit = DEPOSITS.load(deps.storage, (&sender, denom.as_str())).unwrap();
-    deposit.coins.amount = deposit.coins.amount.checked_sub(Uint128::from(amount)).unwrap();
-    deposit.count = deposit.count.checked_sub(1).unwrap();
-    DEPOSITS.save(deps.storage, (&sender, denom.as_str()), &deposit).unwrap();
+    withdraw_native(deps.storage, &env.block, &info.sender, &denom, amount)?;
 
     let msg = BankMsg::Send {
-        to_address: sender.clone(),
-        amount: vec![coin(amount, denom.clone())],
+        to_address: recipient.to_string(),
+        amount: vec![coin(amount.u128(), denom.clone())],
     };
 
     Ok(Response::new()
         .add_attribute("execute", "withdraw")
         .add_attribute("denom", denom)
-        .add_attribute("amount", amount.to_string())
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient)
         .add_message(msg)
     )
 }
 
-pub fn execute_cw20_deposit(deps: DepsMut, env: Env, info: MessageInfo, owner:String, amount:Uint128) -> Result<Response, ContractError> {
-    let cw20_contract_address = info.sender.clone().into_string();
-    //check to see if u
-    let expired_at = Expiration::AtHeight(env.block.height + 20);
-    
-    match CW20_DEPOSITS.load(deps.storage, (&owner, &cw20_contract_address)) {
-        Ok(mut deposit) => {
-            //add coins to their account
-
-            //TODO update time of stake when new coins are added.
-
-            deposit.amount = deposit.amount.checked_add(amount).unwrap();
-            deposit.count = deposit.count.checked_add(1).unwrap();
-            deposit.stake_time = expired_at;
-            CW20_DEPOSITS
-                .save(deps.storage, (&owner, &cw20_contract_address), &deposit)
-                .unwrap();
-        }
-        Err(_) => {
-            //user does not exist, add them.
-            let deposit = Cw20Deposits {
-                count: 1,
-                owner: owner.clone(),
-                contract:info.sender.into_string(),
-                amount,
-                stake_time: expired_at,
-            };
-            CW20_DEPOSITS
-                .save(deps.storage, (&owner, &cw20_contract_address), &deposit)
-                .unwrap();
-        }
+pub fn execute_withdraw_all(deps: DepsMut, env: Env, info: MessageInfo, denom: String) -> Result<Response, ContractError> {
+    let amount = balance_of(deps.storage, &info.sender, &AssetInfo::Native { denom: denom.clone() })?;
+    if amount.is_zero() {
+        return Err(ContractError::NoDepositForDenom { denom });
+    }
+
+    withdraw_native(deps.storage, &env.block, &info.sender, &denom, amount)?;
+
+    let msg = BankMsg::Send {
+        to_address: info.sender.to_string(),
+        amount: vec![coin(amount.u128(), denom.clone())],
+    };
+
+    Ok(Response::new()
+        .add_attribute("execute", "withdraw_all")
+        .add_attribute("denom", denom)
+        .add_attribute("amount", amount)
+        .add_message(msg)
+    )
+}
+
+pub fn execute_transfer_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    denom: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if recipient == info.sender {
+        return Err(ContractError::CannotTransferToSelf {});
+    }
+    if amount.is_zero() || denom.is_empty() {
+        return Err(ContractError::InvalidCoin {});
     }
+
+    //only the ledger moves, the coins stay in the contract so the totals do not change
+    debit_native(deps.storage, &env.block, &info.sender, &denom, amount)?;
+    let limits = ALLOWED_DENOMS.may_load(deps.storage, &denom)?;
+    credit_native(deps.storage, &env.block, &recipient, &coin(amount.u128(), denom.clone()), limits.as_ref())?;
+    DEPOSITORS.save(deps.storage, &recipient, &Empty {})?;
+
     Ok(Response::new()
+        .add_attribute("execute", "transfer_deposit")
+        .add_attribute("denom", denom)
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient))
+}
+
+// takes `amount` out of the native deposit of an owner and out of the denom total
+fn withdraw_native(storage: &mut dyn Storage, block: &BlockInfo, owner: &Addr, denom: &str, amount: Uint128) -> Result<(), ContractError> {
+    debit_native(storage, block, owner, denom, amount)?;
+    DENOM_TOTALS.update(storage, denom, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_sub(amount)?)
+    })?;
+    Ok(())
+}
+
+// takes `amount` out of the native deposit of an owner, removing the record once it is empty
+fn debit_native(storage: &mut dyn Storage, block: &BlockInfo, owner: &Addr, denom: &str, amount: Uint128) -> Result<(), ContractError> {
+    debit_balance(storage, block, owner, &AssetInfo::Native { denom: denom.to_string() }, amount)
+}
+
+// adds `amount` to the balance of an owner in an asset, creating the record when needed
+fn credit_balance(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    asset: &AssetInfo,
+    amount: Uint128,
+) -> Result<Balance, ContractError> {
+    let (kind, id) = balance_key(asset);
+    let mut balance = balances()
+        .may_load(storage, (owner, kind, id))?
+        .unwrap_or_else(|| Balance {
+            owner: owner.clone(),
+            asset: asset.clone(),
+            amount: Uint128::zero(),
+            deposit_count: 0,
+            withdraw_count: 0,
+            last_deposit: None,
+            last_withdraw: None,
+        });
+    balance.amount = balance.amount.checked_add(amount)?;
+    balance.deposit_count = increment(balance.deposit_count)?;
+    balance.last_deposit = Some(BlockStamp::from(block));
+    balances().save(storage, (owner, kind, id), &balance)?;
+    Ok(balance)
+}
+
+// takes `amount` out of the balance of an owner in an asset, removing the record once it is empty
+fn debit_balance(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    asset: &AssetInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let (kind, id) = balance_key(asset);
+    let mut balance = balances()
+        .may_load(storage, (owner, kind, id))?
+        .ok_or_else(|| match asset {
+            AssetInfo::Native { denom } => ContractError::NoDepositForDenom { denom: denom.clone() },
+            AssetInfo::Cw20 { .. } => ContractError::NoCw20ToWithdraw {},
+        })?;
+    if balance.amount < amount {
+        return Err(ContractError::InsufficientFunds {
+            requested: amount,
+            available: balance.amount,
+        });
+    }
+    balance.amount = balance.amount.checked_sub(amount)?;
+    balance.withdraw_count = increment(balance.withdraw_count)?;
+    balance.last_withdraw = Some(BlockStamp::from(block));
+    if balance.amount.is_zero() {
+        balances().remove(storage, (owner, kind, id))?;
+    } else {
+        balances().save(storage, (owner, kind, id), &balance)?;
+    }
+    Ok(())
+}
+
+fn balance_of(storage: &dyn Storage, owner: &Addr, asset: &AssetInfo) -> StdResult<Uint128> {
+    let (kind, id) = balance_key(asset);
+    Ok(balances()
+        .may_load(storage, (owner, kind, id))?
+        .map(|balance| balance.amount)
+        .unwrap_or_default())
+}
+
+fn cw20_asset(contract: &Addr) -> AssetInfo {
+    AssetInfo::Cw20 { contract: contract.to_string() }
+}
+
+// books the cw20 sent with `cw20_msg` for `owner`, who is the sender unless deposited for
+pub fn execute_cw20_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+    owner: String,
+    lock_tier: Option<String>,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let cw20_contract_address = info.sender;
+    let amount = cw20_msg.amount;
+    let owner = deps.api.addr_validate(&owner).map_err(|_| ContractError::InvalidOwner {})?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+    validate_memo(memo.as_deref())?;
+
+    //only allowed cw20 contracts can book deposits
+    let limits = ALLOWED_CW20S
+        .may_load(deps.storage, &cw20_contract_address)?
+        .ok_or_else(|| ContractError::TokenNotAllowed { contract: cw20_contract_address.to_string() })?;
+    if let Some(min) = limits.min_deposit {
+        if amount < min {
+            return Err(ContractError::DepositTooSmall { asset: cw20_contract_address.into_string(), min });
+        }
+    }
+    if let Some(max) = limits.max_deposit {
+        if amount > max {
+            return Err(ContractError::DepositTooLarge { asset: cw20_contract_address.into_string(), max });
+        }
+    }
+
+    //a tier replaces the lock of the cw20 and weighs the deposit in the rewards
+    let (lock_duration, reward_multiplier) = match &lock_tier {
+        Some(name) => {
+            let tier = LOCK_TIERS
+                .may_load(deps.storage, name)?
+                .ok_or_else(|| ContractError::UnknownLockTier { name: name.clone() })?;
+            (tier.duration, tier.reward_multiplier)
+        }
+        None => (cw20_lock_duration(deps.as_ref(), &cw20_contract_address)?, Decimal::one()),
+    };
+
+    //every deposit gets its own lot, so topping up never re-locks older lots. Deposits with the
+    //same lock in the same block share one
+    let expired_at = lock_duration.after(&env.block);
+    DEPOSITORS.save(deps.storage, &owner, &Empty {})?;
+
+    //the multiplier only pays while the lot is locked
+    let weight = match expired_at.is_expired(&env.block) {
+        true => amount,
+        false => lot_weight(amount, reward_multiplier)?,
+    };
+    let max_lots = if owner == cw20_msg.sender { MAX_LOTS } else { MAX_LOTS_FROM_OTHERS };
+    let lot = Cw20Lot {
+        id: 0,
+        amount,
+        deposited_at: BlockStamp::from(&env.block),
+        unlocks_at: expired_at,
+        lock_tier,
+        weight,
+        memo,
+    };
+    let lot_ids = credit_cw20(deps.storage, &env.block, &owner, &cw20_contract_address, vec![lot.clone()], max_lots)?;
+    increase_cw20_totals(deps.storage, &cw20_contract_address, amount, weight)?;
+    let mut res = Response::new()
         .add_attribute("execute", "cw20_deposit")
-        .add_attribute("owner", owner)
-        .add_attribute("contract", cw20_contract_address.to_string())
-        .add_attribute("amount", amount.to_string()))
+        .add_attribute("owner", owner.as_str())
+        .add_attribute("contract", cw20_contract_address)
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("lot_id", lot_ids[0].to_string())
+        .add_attribute("unlocks_at", expired_at.to_string());
+    if owner != cw20_msg.sender {
+        res = res.add_attribute("depositor", cw20_msg.sender);
+    }
+    if let Some(lock_tier) = lot.lock_tier {
+        res = res.add_attribute("lock_tier", lock_tier);
+    }
+    if let Some(memo) = lot.memo {
+        res = res.add_attribute("memo", memo);
+    }
+    Ok(res)
+}
+
+// adds lots to the cw20 deposit of an owner, creating the record when needed. The owner is
+// settled before the lots are stored, so matured lots they join are reweighted first. Returns
+// the ids of the lots holding them, the totals are updated after this.
+fn credit_cw20(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    contract: &Addr,
+    lots: Vec<Cw20Lot>,
+    max_lots: usize,
+) -> Result<Vec<u64>, ContractError> {
+    rewards::settle(storage, block, owner, contract)?;
+    let amount = lots.iter().map(|lot| lot.amount).sum::<Uint128>();
+    let weight = lots_weight(&lots);
+    let ids = lots
+        .into_iter()
+        .map(|lot| store_cw20_lot(storage, owner, contract, lot, max_lots))
+        .collect::<Result<_, _>>()?;
+    CW20_WEIGHTS.update(storage, (owner, contract), |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(weight)?)
+    })?;
+    credit_balance(storage, block, owner, &cw20_asset(contract), amount)?;
+    Ok(ids)
 }
 
 //use WasmMsg::Execute instead of BankMsg::Send
@@ -172,53 +681,1696 @@ pub fn execute_cw20_withdraw(
     info: MessageInfo,
     contract:String,
     amount: Uint128,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let contract = deps.api.addr_validate(&contract)?;
+    let recipient = maybe_addr(deps.api, recipient)?.unwrap_or_else(|| info.sender.clone());
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+
+    withdraw_cw20(deps.storage, &env.block, &info.sender, &contract, amount, LotSelection::Matured)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "withdraw")
+        .add_attribute("contract", contract.as_str())
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient.as_str())
+        .add_message(cw20_transfer_msg(&contract, &recipient, amount)?))
+}
+
+pub fn execute_withdraw_all_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
 ) -> Result<Response, ContractError> {
-    let sender = info.sender.clone().into_string();
-    match CW20_DEPOSITS.load(deps.storage, (&sender, &contract)) {
+    let contract = deps.api.addr_validate(&contract)?;
+    if !balances().has(deps.storage, (&info.sender, CW20_KIND, contract.as_str())) {
+        return Err(ContractError::NoCw20ToWithdraw {});
+    }
+    let amount = withdrawable_cw20(deps.as_ref(), &env, &info.sender, &contract)?;
+    if amount.is_zero() {
+        return Err(ContractError::StakeDurationNotPassed {});
+    }
+
+    withdraw_cw20(deps.storage, &env.block, &info.sender, &contract, amount, LotSelection::Matured)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "withdraw_all_cw20")
+        .add_attribute("contract", contract.as_str())
+        .add_attribute("amount", amount)
+        .add_message(cw20_transfer_msg(&contract, &info.sender, amount)?))
+}
 
-        //TODO: make sure the stake duration has passed before allowing withdraw.
-        //if duration is not past yet return error.
+pub fn execute_early_withdraw_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let policy = config.early_withdraw.ok_or(ContractError::EarlyWithdrawDisabled {})?;
+    let contract = deps.api.addr_validate(&contract)?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
 
-        Ok(mut deposit) => {
-            //add coins to their account
+    //matured lots go first, they carry no penalty
+    let lots = withdraw_cw20(deps.storage, &env.block, &info.sender, &contract, amount, LotSelection::MaturedFirst)?;
+    let penalty = lots
+        .iter()
+        .map(|lot| lot.amount * (policy.max_penalty * remaining_lock_share(lot, &env.block)))
+        .sum::<Uint128>();
+    let payout = amount.checked_sub(penalty)?;
 
-            if deposit.stake_time.is_expired(&env.block) != true {
-                return Err(ContractError::StakeDurationNotPassed {  });
+    let mut res = Response::new()
+        .add_attribute("execute", "early_withdraw_cw20")
+        .add_attribute("contract", contract.as_str())
+        .add_attribute("amount", payout)
+        .add_attribute("penalty", penalty);
+    if !payout.is_zero() {
+        res = res.add_message(cw20_transfer_msg(&contract, &info.sender, payout)?);
+    }
+    if !penalty.is_zero() {
+        let treasury = match policy.destination {
+            PenaltyDestination::Treasury { address } => Some(address),
+            //with nobody left to share with, the penalty goes to the admin
+            PenaltyDestination::Stakers {} => {
+                match rewards::distribute_penalty(deps.storage, &contract, &info.sender, penalty)? {
+                    true => None,
+                    false => Some(config.admin),
+                }
             }
+        };
+        if let Some(treasury) = treasury {
+            res = res
+                .add_attribute("penalty_recipient", treasury.as_str())
+                .add_message(cw20_transfer_msg(&contract, &treasury, penalty)?);
+        }
+    }
+    Ok(res)
+}
+
+pub fn execute_unbond_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let period = CONFIG.load(deps.storage)?.unbonding_period.ok_or(ContractError::UnbondingDisabled {})?;
+    let contract = deps.api.addr_validate(&contract)?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+
+    //the unbonding period stands in for the default lock, a tier lock has to run its course.
+    //Unbonding cw20 is no longer a deposit, so it stops counting for the totals and rewards
+    let lots = debit_cw20(deps.storage, &env.block, &info.sender, &contract, amount, LotSelection::Unbondable)?;
+    decrease_cw20_totals(deps.storage, &contract, amount, lots_weight(&lots))?;
+
+    let release_at = period.after(&env.block);
+    let id = next_claim_id(deps.storage)?;
+    CW20_CLAIMS.save(deps.storage, (&info.sender, id), &Cw20Claim { id, contract: contract.clone(), amount, release_at })?;
+    CW20_UNBONDING_TOTALS.update(deps.storage, &contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "unbond_cw20")
+        .add_attribute("claim_id", id.to_string())
+        .add_attribute("contract", contract)
+        .add_attribute("amount", amount)
+        .add_attribute("release_at", release_at.to_string()))
+}
+
+pub fn execute_claim_cw20(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    //the unbonding period can change, so a later claim may be released first
+    let released = CW20_CLAIMS
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|claim| claim.map(|(_, claim)| claim))
+        .filter(|claim| claim.as_ref().map_or(true, |claim| claim.release_at.is_expired(&env.block)))
+        .collect::<StdResult<Vec<_>>>()?;
+    if released.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    for claim in &released {
+        CW20_CLAIMS.remove(deps.storage, (&info.sender, claim.id));
+    }
+
+    //one transfer per cw20
+    let mut payouts: Vec<(Addr, Uint128)> = vec![];
+    for claim in released {
+        match payouts.iter_mut().find(|(contract, _)| *contract == claim.contract) {
+            Some((_, amount)) => *amount = amount.checked_add(claim.amount)?,
+            None => payouts.push((claim.contract, claim.amount)),
+        }
+    }
+    let mut res = Response::new().add_attribute("execute", "claim_cw20");
+    for (contract, amount) in payouts {
+        CW20_UNBONDING_TOTALS.update(deps.storage, &contract, |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default().checked_sub(amount)?)
+        })?;
+        res = res
+            .add_attribute("contract", contract.as_str())
+            .add_attribute("amount", amount)
+            .add_message(cw20_transfer_msg(&contract, &info.sender, amount)?);
+    }
+    Ok(res)
+}
 
-            deposit.amount = deposit.amount.checked_sub(amount).unwrap();
-            deposit.count = deposit.count.checked_sub(1).unwrap();
-            CW20_DEPOSITS
-                .save(deps.storage, (&sender, &contract), &deposit)
-                .unwrap();
+pub fn execute_withdraw_everything(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut res = Response::new().add_attribute("execute", "withdraw_everything");
 
-            let exe_msg = cw20_base::msg::ExecuteMsg::Transfer { recipient: sender, amount: Uint128::from(amount) };
-            let msg = WasmMsg::Execute { contract_addr: contract, msg: to_binary(&exe_msg)?, funds:vec![] };
+    let native: Vec<Coin> = balances()
+        .prefix((&info.sender, NATIVE_KIND))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, balance)| coin(balance.amount.u128(), denom)))
+        .collect::<StdResult<_>>()?;
+    for coins in &native {
+        withdraw_native(deps.storage, &env.block, &info.sender, &coins.denom, coins.amount)?;
+        res = res
+            .add_attribute("denom", coins.denom.as_str())
+            .add_attribute("amount", coins.amount);
+    }
+    if !native.is_empty() {
+        res = res.add_message(BankMsg::Send { to_address: info.sender.to_string(), amount: native });
+    }
 
-            Ok(Response::new()
-            .add_attribute("execute", "withdraw")
-            .add_message(msg))
+    //with unbonding, cw20 stays until it is unbonded
+    let contracts: Vec<Addr> = match CONFIG.load(deps.storage)?.unbonding_period {
+        Some(_) => vec![],
+        None => balances()
+            .prefix((&info.sender, CW20_KIND))
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|contract| contract.map(Addr::unchecked))
+            .collect::<StdResult<_>>()?,
+    };
+    for contract in contracts {
+        let amount = withdrawable_cw20(deps.as_ref(), &env, &info.sender, &contract)?;
+        if amount.is_zero() {
+            continue;
         }
-        Err(_) => {
-            return Err(ContractError::NoCw20ToWithdraw {  });
+        withdraw_cw20(deps.storage, &env.block, &info.sender, &contract, amount, LotSelection::Matured)?;
+        res = res
+            .add_attribute("contract", contract.as_str())
+            .add_attribute("amount", amount)
+            .add_message(cw20_transfer_msg(&contract, &info.sender, amount)?);
+    }
+
+    if res.messages.is_empty() {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+    Ok(res)
+}
+
+pub fn execute_transfer_cw20_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    contract: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    if recipient == info.sender {
+        return Err(ContractError::CannotTransferToSelf {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+
+    //lots move with their lock, so locked tokens stay locked for the recipient. They count as
+    //deposits of others for the lots of the recipient
+    let lots = debit_cw20(deps.storage, &env.block, &info.sender, &contract, amount, LotSelection::OldestFirst)?;
+    credit_cw20(deps.storage, &env.block, &recipient, &contract, lots, MAX_LOTS_FROM_OTHERS)?;
+    DEPOSITORS.save(deps.storage, &recipient, &Empty {})?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "transfer_cw20_deposit")
+        .add_attribute("contract", contract)
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient))
+}
+
+// takes `amount` out of the cw20 lots of an owner and out of the cw20 total, for cw20 leaving
+// the contract without going through unbonding
+fn withdraw_cw20(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    contract: &Addr,
+    amount: Uint128,
+    selection: LotSelection,
+) -> Result<Vec<Cw20Lot>, ContractError> {
+    if CONFIG.load(storage)?.unbonding_period.is_some() {
+        return Err(ContractError::UnbondingRequired {});
+    }
+    let lots = debit_cw20(storage, block, owner, contract, amount, selection)?;
+    decrease_cw20_totals(storage, contract, amount, lots_weight(&lots))?;
+    Ok(lots)
+}
+
+fn increase_cw20_totals(storage: &mut dyn Storage, contract: &Addr, amount: Uint128, weight: Uint128) -> StdResult<()> {
+    CW20_TOTALS.update(storage, contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    CW20_WEIGHTED_TOTALS.update(storage, contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(weight)?)
+    })?;
+    Ok(())
+}
+
+fn decrease_cw20_totals(storage: &mut dyn Storage, contract: &Addr, amount: Uint128, weight: Uint128) -> StdResult<()> {
+    CW20_TOTALS.update(storage, contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_sub(amount)?)
+    })?;
+    CW20_WEIGHTED_TOTALS.update(storage, contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_sub(weight)?)
+    })?;
+    Ok(())
+}
+
+// adds a lot to the lots of an owner, ignoring its id. It is merged into a lot with the same
+// deposit block, lock and memo when there is one, otherwise it gets a new id as long as the
+// owner holds fewer than `max_lots`. Returns the id of the lot holding the amount.
+fn store_cw20_lot(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    contract: &Addr,
+    lot: Cw20Lot,
+    max_lots: usize,
+) -> Result<u64, ContractError> {
+    let mut count = 0;
+    let mut same_lock = None;
+    for existing in CW20_LOTS.prefix((owner, contract)).range(storage, None, None, Order::Ascending).take(MAX_LOTS) {
+        let (_, existing) = existing?;
+        count += 1;
+        if existing.deposited_at == lot.deposited_at
+            && existing.unlocks_at == lot.unlocks_at
+            && existing.lock_tier == lot.lock_tier
+            && existing.memo == lot.memo
+        {
+            same_lock = Some(existing);
+            break;
         }
     }
+
+    let lot = match same_lock {
+        Some(existing) => Cw20Lot {
+            amount: existing.amount.checked_add(lot.amount)?,
+            weight: existing.weight.checked_add(lot.weight)?,
+            ..existing
+        },
+        None if count >= max_lots => return Err(ContractError::TooManyLots { max: max_lots }),
+        None => Cw20Lot { id: next_lot_id(storage)?, ..lot },
+    };
+    CW20_LOTS.save(storage, (owner, contract, lot.id), &lot)?;
+    Ok(lot.id)
 }
 
+// weight of a lot of `amount` deposited with `reward_multiplier`
+fn lot_weight(amount: Uint128, reward_multiplier: Decimal) -> Result<Uint128, ContractError> {
+    amount
+        .checked_multiply_ratio(reward_multiplier.numerator(), reward_multiplier.denominator())
+        .map_err(|_| OverflowError::new(OverflowOperation::Mul, amount, reward_multiplier).into())
+}
 
-pub fn query_deposits(deps: Deps, address:String) -> StdResult<DepositResponse> {
-    let res: StdResult<Vec<_>> = DEPOSITS.prefix(&address).range(deps.storage, None, None, Order::Ascending).collect();
-    let deposits = res?;
-    Ok(DepositResponse { deposits })
+fn lots_weight(lots: &[Cw20Lot]) -> Uint128 {
+    lots.iter().map(|lot| lot.weight).sum()
 }
 
-fn query_cw20_deposits(deps: Deps, address: String) -> StdResult<Cw20DepositResponse> {
-    let res: StdResult<Vec<_>> = CW20_DEPOSITS
-        .prefix(&address)
-        .range(deps.storage, None, None, Order::Ascending)
-        .collect();
-    let deposits = res?;
-    Ok(Cw20DepositResponse { deposits })
+// takes `amount` out of the cw20 deposit of an owner, removing the record once it is empty.
+// Returns the parts of the lots taken, the totals are updated after this.
+fn debit_cw20(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    contract: &Addr,
+    amount: Uint128,
+    selection: LotSelection,
+) -> Result<Vec<Cw20Lot>, ContractError> {
+    rewards::settle(storage, block, owner, contract)?;
+    let asset = cw20_asset(contract);
+    let available = balances()
+        .may_load(storage, (owner, CW20_KIND, contract.as_str()))?
+        .ok_or(ContractError::NoCw20ToWithdraw {})?
+        .amount;
+    if available < amount {
+        return Err(ContractError::InsufficientFunds { requested: amount, available });
+    }
+    let lots = take_cw20_lots(storage, block, owner, contract, amount, selection)?;
+    let weight = CW20_WEIGHTS
+        .may_load(storage, (owner, contract))?
+        .unwrap_or_default()
+        .checked_sub(lots_weight(&lots))?;
+    if weight.is_zero() {
+        CW20_WEIGHTS.remove(storage, (owner, contract));
+    } else {
+        CW20_WEIGHTS.save(storage, (owner, contract), &weight)?;
+    }
+
+    debit_balance(storage, block, owner, &asset, amount)?;
+    Ok(lots)
 }
 
+fn cw20_transfer_msg(contract: &Addr, recipient: &Addr, amount: Uint128) -> StdResult<WasmMsg> {
+    let exe_msg = cw20_base::msg::ExecuteMsg::Transfer { recipient: recipient.to_string(), amount };
+    Ok(WasmMsg::Execute { contract_addr: contract.to_string(), msg: to_binary(&exe_msg)?, funds:vec![] })
+}
+
+// books the cw1155 tokens sent to the contract, one lot per token id of the batch
+pub fn execute_cw1155_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    operator: String,
+    from: Option<String>,
+    batch: Vec<(String, Uint128)>,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    let contract = info.sender;
+    //any contract can call the receive hooks, only allowed ones can book deposits
+    if !ALLOWED_CW1155S.has(deps.storage, &contract) {
+        return Err(ContractError::Cw1155NotAllowed { contract: contract.into_string() });
+    }
+    let depositor = from.unwrap_or(operator);
+    let (owner, lock_tier, memo) = match from_binary(&msg) {
+        Ok(Cw1155HookMsg::Deposit { lock_tier }) => (depositor.clone(), lock_tier, None),
+        Ok(Cw1155HookMsg::DepositFor { beneficiary, memo, lock_tier }) => (beneficiary, lock_tier, memo),
+        Err(_) => return Err(ContractError::CustomError { val: "Invalid Cw1155HookMsg".to_string() }),
+    };
+    let owner = deps.api.addr_validate(&owner).map_err(|_| ContractError::InvalidOwner {})?;
+    if batch.is_empty() || batch.iter().any(|(token_id, amount)| token_id.is_empty() || amount.is_zero()) {
+        return Err(ContractError::InvalidCoin {});
+    }
+    validate_memo(memo.as_deref())?;
+
+    //same locks as cw20, the tier only picks the duration since cw1155 earns no rewards
+    let lock_duration = match &lock_tier {
+        Some(name) => LOCK_TIERS
+            .may_load(deps.storage, name)?
+            .ok_or_else(|| ContractError::UnknownLockTier { name: name.clone() })?
+            .duration,
+        None => cw20_lock_duration(deps.as_ref(), &contract)?,
+    };
+    let unlocks_at = lock_duration.after(&env.block);
+    DEPOSITORS.save(deps.storage, &owner, &Empty {})?;
+    let max_lots = if owner == depositor { MAX_LOTS } else { MAX_LOTS_FROM_OTHERS };
 
+    let mut res = Response::new()
+        .add_attribute("execute", "cw1155_deposit")
+        .add_attribute("owner", owner.as_str())
+        .add_attribute("contract", contract.as_str())
+        .add_attribute("unlocks_at", unlocks_at.to_string());
+    if owner != depositor {
+        res = res.add_attribute("depositor", depositor);
+    }
+    if let Some(lock_tier) = &lock_tier {
+        res = res.add_attribute("lock_tier", lock_tier);
+    }
+    if let Some(memo) = &memo {
+        res = res.add_attribute("memo", memo);
+    }
+    for (token_id, amount) in batch {
+        let lot = Cw1155Lot {
+            id: 0,
+            amount,
+            deposited_at: BlockStamp::from(&env.block),
+            unlocks_at,
+            lock_tier: lock_tier.clone(),
+            memo: memo.clone(),
+        };
+        let lot_id = store_cw1155_lot(deps.storage, &owner, &contract, &token_id, lot, max_lots)?;
+        CW1155_BALANCES.update(deps.storage, (&owner, &contract, &token_id), |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_add(amount)?)
+        })?;
+        res = res
+            .add_attribute("token_id", token_id)
+            .add_attribute("amount", amount)
+            .add_attribute("lot_id", lot_id.to_string());
+    }
+    Ok(res)
+}
+
+// adds a lot to the lots of an owner for a token id, merged or capped like cw20 lots
+fn store_cw1155_lot(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    contract: &Addr,
+    token_id: &str,
+    lot: Cw1155Lot,
+    max_lots: usize,
+) -> Result<u64, ContractError> {
+    let mut count = 0;
+    let mut same_lock = None;
+    for existing in CW1155_LOTS
+        .prefix((owner, (contract, token_id)))
+        .range(storage, None, None, Order::Ascending)
+        .take(MAX_LOTS)
+    {
+        let (_, existing) = existing?;
+        count += 1;
+        if existing.deposited_at == lot.deposited_at
+            && existing.unlocks_at == lot.unlocks_at
+            && existing.lock_tier == lot.lock_tier
+            && existing.memo == lot.memo
+        {
+            same_lock = Some(existing);
+            break;
+        }
+    }
+
+    let lot = match same_lock {
+        Some(existing) => Cw1155Lot { amount: existing.amount.checked_add(lot.amount)?, ..existing },
+        None if count >= max_lots => return Err(ContractError::TooManyLots { max: max_lots }),
+        None => Cw1155Lot { id: next_lot_id(storage)?, ..lot },
+    };
+    CW1155_LOTS.save(storage, (owner, (contract, token_id), lot.id), &lot)?;
+    Ok(lot.id)
+}
+
+pub fn execute_cw1155_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    token_id: String,
+    amount: Uint128,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let contract = deps.api.addr_validate(&contract)?;
+    let recipient = maybe_addr(deps.api, recipient)?.unwrap_or_else(|| info.sender.clone());
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+
+    let key = (&info.sender, &contract, token_id.as_str());
+    let available = CW1155_BALANCES
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::NoCw1155ToWithdraw {})?;
+    if available < amount {
+        return Err(ContractError::InsufficientFunds { requested: amount, available });
+    }
+    take_cw1155_lots(deps.storage, &env.block, &info.sender, &contract, &token_id, amount)?;
+    let balance = available - amount;
+    if balance.is_zero() {
+        CW1155_BALANCES.remove(deps.storage, key);
+    } else {
+        CW1155_BALANCES.save(deps.storage, key, &balance)?;
+    }
+
+    let exe_msg = Cw1155ExecuteMsg::SendFrom {
+        from: env.contract.address.to_string(),
+        to: recipient.to_string(),
+        token_id: token_id.clone(),
+        value: amount,
+        msg: None,
+    };
+    Ok(Response::new()
+        .add_attribute("execute", "cw1155_withdraw")
+        .add_attribute("contract", contract.as_str())
+        .add_attribute("token_id", token_id)
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient.as_str())
+        .add_message(WasmMsg::Execute { contract_addr: contract.to_string(), msg: to_binary(&exe_msg)?, funds: vec![] }))
+}
+
+// sum of the cw1155 lots of an owner for a token id whose lock has expired
+fn withdrawable_cw1155(storage: &dyn Storage, block: &BlockInfo, owner: &Addr, contract: &Addr, token_id: &str) -> StdResult<Uint128> {
+    CW1155_LOTS
+        .prefix((owner, (contract, token_id)))
+        .range(storage, None, None, Order::Ascending)
+        .filter(|lot| match lot {
+            Ok((_, lot)) => lot.unlocks_at.is_expired(block),
+            Err(_) => true,
+        })
+        .try_fold(Uint128::zero(), |acc, lot| Ok(acc + lot?.1.amount))
+}
+
+// takes `amount` out of the matured cw1155 lots of an owner, oldest first, reading no more lots
+// than needed. Fails when the matured lots do not cover the amount.
+fn take_cw1155_lots(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    contract: &Addr,
+    token_id: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let mut lots = vec![];
+    let mut covered = Uint128::zero();
+    for lot in CW1155_LOTS.prefix((owner, (contract, token_id))).range(storage, None, None, Order::Ascending) {
+        if covered >= amount {
+            break;
+        }
+        let (_, lot) = lot?;
+        if lot.unlocks_at.is_expired(block) {
+            covered += lot.amount;
+            lots.push(lot);
+        }
+    }
+    if covered < amount {
+        return Err(ContractError::StakeDurationNotPassed {});
+    }
+
+    let mut remaining = amount;
+    for mut lot in lots {
+        if remaining.is_zero() {
+            break;
+        }
+        let part = lot.amount.min(remaining);
+        remaining -= part;
+        if part == lot.amount {
+            CW1155_LOTS.remove(storage, (owner, (contract, token_id), lot.id));
+        } else {
+            lot.amount -= part;
+            CW1155_LOTS.save(storage, (owner, (contract, token_id), lot.id), &lot)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn execute_increase_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    asset: AssetInfo,
+    amount: Uint128,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let spender = deps.api.addr_validate(&spender)?;
+    if spender == info.sender {
+        return Err(ContractError::CannotSetOwnAccount {});
+    }
+    let asset = validate_asset(deps.as_ref(), asset)?;
+    let key = asset_key(&asset);
+
+    let allowance = ALLOWANCES.update(deps.storage, (&info.sender, &spender, &key), |allow| -> Result<_, ContractError> {
+        let mut allow = match allow {
+            //an expired allowance starts over
+            Some(allow) if !allow.expires.is_expired(&env.block) => allow,
+            _ => DepositAllowance { asset: asset.clone(), amount: Uint128::zero(), expires: Expiration::Never {} },
+        };
+        if let Some(expires) = expires {
+            if expires.is_expired(&env.block) {
+                return Err(ContractError::InvalidExpiration {});
+            }
+            allow.expires = expires;
+        }
+        allow.amount = allow.amount.checked_add(amount)?;
+        Ok(allow)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "increase_allowance")
+        .add_attribute("owner", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("asset", key)
+        .add_attribute("allowance", allowance.amount))
+}
+
+pub fn execute_decrease_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    asset: AssetInfo,
+    amount: Uint128,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let spender = deps.api.addr_validate(&spender)?;
+    if spender == info.sender {
+        return Err(ContractError::CannotSetOwnAccount {});
+    }
+    let asset = validate_asset(deps.as_ref(), asset)?;
+    let key = asset_key(&asset);
+
+    let mut allowance = ALLOWANCES
+        .may_load(deps.storage, (&info.sender, &spender, &key))?
+        .ok_or(ContractError::NoAllowance {})?;
+    allowance.amount = allowance.amount.saturating_sub(amount);
+    if allowance.amount.is_zero() {
+        ALLOWANCES.remove(deps.storage, (&info.sender, &spender, &key));
+    } else {
+        if let Some(expires) = expires {
+            if expires.is_expired(&env.block) {
+                return Err(ContractError::InvalidExpiration {});
+            }
+            allowance.expires = expires;
+        }
+        ALLOWANCES.save(deps.storage, (&info.sender, &spender, &key), &allowance)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("execute", "decrease_allowance")
+        .add_attribute("owner", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("asset", key)
+        .add_attribute("allowance", allowance.amount))
+}
+
+pub fn execute_withdraw_from(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    asset: AssetInfo,
+    amount: Uint128,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let recipient = maybe_addr(deps.api, recipient)?.unwrap_or_else(|| info.sender.clone());
+    let asset = validate_asset(deps.as_ref(), asset)?;
+    let key = asset_key(&asset);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+
+    let mut allowance = ALLOWANCES
+        .may_load(deps.storage, (&owner, &info.sender, &key))?
+        .ok_or(ContractError::NoAllowance {})?;
+    if allowance.expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    if allowance.amount < amount {
+        return Err(ContractError::InsufficientAllowance { requested: amount, allowance: allowance.amount });
+    }
+    allowance.amount -= amount;
+    if allowance.amount.is_zero() {
+        ALLOWANCES.remove(deps.storage, (&owner, &info.sender, &key));
+    } else {
+        ALLOWANCES.save(deps.storage, (&owner, &info.sender, &key), &allowance)?;
+    }
+
+    let res = Response::new()
+        .add_attribute("execute", "withdraw_from")
+        .add_attribute("owner", owner.as_str())
+        .add_attribute("spender", info.sender.as_str())
+        .add_attribute("asset", key)
+        .add_attribute("amount", amount)
+        .add_attribute("recipient", recipient.as_str());
+    match asset {
+        AssetInfo::Native { denom } => {
+            withdraw_native(deps.storage, &env.block, &owner, &denom, amount)?;
+            Ok(res.add_message(BankMsg::Send { to_address: recipient.into_string(), amount: vec![coin(amount.u128(), denom)] }))
+        }
+        AssetInfo::Cw20 { contract } => {
+            let contract = Addr::unchecked(contract);
+            withdraw_cw20(deps.storage, &env.block, &owner, &contract, amount, LotSelection::Matured)?;
+            Ok(res.add_message(cw20_transfer_msg(&contract, &recipient, amount)?))
+        }
+    }
+}
+
+pub fn execute_set_reward_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    reward: AssetInfo,
+    emission_per_second: Uint128,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    let reward = validate_asset(deps.as_ref(), reward)?;
+
+    let pool = match REWARD_POOLS.may_load(deps.storage, &contract)? {
+        Some(mut pool) => {
+            if pool.reward != reward {
+                return Err(ContractError::WrongRewardAsset { expected: asset_key(&pool.reward) });
+            }
+            //rewards up to now are emitted at the old rate
+            let total_staked = rewards::total_weight(deps.storage, &contract)?;
+            rewards::accrue(&mut pool, env.block.time, total_staked)?;
+            pool.emission_per_second = emission_per_second;
+            pool
+        }
+        None => RewardPool {
+            reward,
+            emission_per_second,
+            undistributed: Uint128::zero(),
+            reward_per_token: Decimal256::zero(),
+            last_update: env.block.time,
+        },
+    };
+    REWARD_POOLS.save(deps.storage, &contract, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "set_reward_pool")
+        .add_attribute("contract", contract)
+        .add_attribute("reward", asset_key(&pool.reward))
+        .add_attribute("emission_per_second", emission_per_second))
+}
+
+pub fn execute_fund_rewards(
+    deps: DepsMut,
+    env: Env,
+    funder: Addr,
+    contract: String,
+    reward: AssetInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if funder != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    let contract = deps.api.addr_validate(&contract)?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidCoin {});
+    }
+
+    let mut pool = REWARD_POOLS
+        .may_load(deps.storage, &contract)?
+        .ok_or_else(|| ContractError::NoRewardPool { contract: contract.to_string() })?;
+    if pool.reward != reward {
+        return Err(ContractError::WrongRewardAsset { expected: asset_key(&pool.reward) });
+    }
+    //emission paused while the pool was empty does not catch up
+    let total_staked = rewards::total_weight(deps.storage, &contract)?;
+    rewards::accrue(&mut pool, env.block.time, total_staked)?;
+    pool.undistributed = pool.undistributed.checked_add(amount)?;
+    REWARD_POOLS.save(deps.storage, &contract, &pool)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "fund_rewards")
+        .add_attribute("contract", contract)
+        .add_attribute("reward", asset_key(&pool.reward))
+        .add_attribute("amount", amount))
+}
+
+pub fn execute_claim_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut res = Response::new().add_attribute("execute", "claim_rewards");
+
+    for contract in rewarded_contracts(deps.storage, &info.sender)? {
+        for (asset, amount) in rewards::take_pending(deps.storage, &env.block, &info.sender, &contract)? {
+            if amount.is_zero() {
+                continue;
+            }
+            //payouts are outflows, halted by the withdraw switch of the kind of reward
+            let operation = match &asset {
+                AssetInfo::Native { .. } => Operation::NativeWithdraw,
+                AssetInfo::Cw20 { .. } => Operation::Cw20Withdraw,
+            };
+            assert_not_paused(deps.as_ref(), operation)?;
+            res = res
+                .add_attribute("contract", contract.as_str())
+                .add_attribute("reward", asset_key(&asset))
+                .add_attribute("amount", amount);
+            res = match asset {
+                AssetInfo::Native { denom } => res.add_message(BankMsg::Send {
+                    to_address: info.sender.to_string(),
+                    amount: vec![coin(amount.u128(), denom)],
+                }),
+                AssetInfo::Cw20 { contract } => {
+                    res.add_message(cw20_transfer_msg(&Addr::unchecked(contract), &info.sender, amount)?)
+                }
+            };
+        }
+    }
+
+    if res.messages.is_empty() {
+        return Err(ContractError::NoRewardsToClaim {});
+    }
+    Ok(res)
+}
+
+pub fn execute_reweight_cw20(deps: DepsMut, env: Env, address: String, contract: String) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&address)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    rewards::settle(deps.storage, &env.block, &owner, &contract)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "reweight_cw20")
+        .add_attribute("owner", owner)
+        .add_attribute("contract", contract))
+}
+
+// cw20 contracts an owner has a deposit, unclaimed rewards or penalty shares for, in order
+fn rewarded_contracts(storage: &dyn Storage, owner: &Addr) -> StdResult<Vec<Addr>> {
+    let mut contracts: Vec<Addr> = balances()
+        .prefix((owner, CW20_KIND))
+        .keys(storage, None, None, Order::Ascending)
+        .map(|contract| contract.map(Addr::unchecked))
+        .chain(USER_REWARDS.prefix(owner).keys(storage, None, None, Order::Ascending))
+        .chain(USER_PENALTIES.prefix(owner).keys(storage, None, None, Order::Ascending))
+        .collect::<StdResult<_>>()?;
+    contracts.sort();
+    contracts.dedup();
+    Ok(contracts)
+}
+
+// normalizes the cw20 address, so the asset always maps to the same storage key
+fn validate_asset(deps: Deps, asset: AssetInfo) -> Result<AssetInfo, ContractError> {
+    match asset {
+        AssetInfo::Native { denom } if denom.is_empty() => Err(ContractError::InvalidCoin {}),
+        AssetInfo::Native { denom } => Ok(AssetInfo::Native { denom }),
+        AssetInfo::Cw20 { contract } => Ok(AssetInfo::Cw20 { contract: deps.api.addr_validate(&contract)?.into_string() }),
+    }
+}
+
+fn validate_memo(memo: Option<&str>) -> Result<(), ContractError> {
+    match memo {
+        Some(memo) if memo.len() > MAX_MEMO_LENGTH => Err(ContractError::MemoTooLong { max: MAX_MEMO_LENGTH }),
+        _ => Ok(()),
+    }
+}
+
+// the asset of a withdraw, given either as an asset or as a native denom
+fn withdrawn_asset(asset: Option<AssetInfo>, denom: Option<String>) -> Result<AssetInfo, ContractError> {
+    match (asset, denom) {
+        (Some(asset), None) => Ok(asset),
+        (None, Some(denom)) => Ok(AssetInfo::Native { denom }),
+        _ => Err(ContractError::InvalidAsset {}),
+    }
+}
+
+fn increment(count: u64) -> Result<u64, OverflowError> {
+    count.checked_add(1).ok_or_else(|| OverflowError::new(OverflowOperation::Add, count, 1))
+}
+
+// sum of the lots of an owner whose lock has expired
+fn withdrawable_cw20(deps: Deps, env: &Env, owner: &Addr, contract: &Addr) -> StdResult<Uint128> {
+    withdrawable_cw20_from(deps.storage, &env.block, owner, contract)
+}
+
+fn withdrawable_cw20_from(storage: &dyn Storage, block: &BlockInfo, owner: &Addr, contract: &Addr) -> StdResult<Uint128> {
+    CW20_LOTS
+        .prefix((owner, contract))
+        .range(storage, None, None, Order::Ascending)
+        .filter(|lot| match lot {
+            Ok((_, lot)) => lot.unlocks_at.is_expired(block),
+            Err(_) => true,
+        })
+        .try_fold(Uint128::zero(), |acc, lot| Ok(acc + lot?.1.amount))
+}
+
+// which lots a debit takes from
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LotSelection {
+    /// matured lots only, oldest first
+    Matured,
+    /// matured lots, then locked lots, oldest first
+    MaturedFirst,
+    /// every lot, oldest first
+    OldestFirst,
+    /// lots with the default lock and matured tier lots, oldest first
+    Unbondable,
+}
+
+impl LotSelection {
+    fn can_take(self, lot: &Cw20Lot, block: &BlockInfo) -> bool {
+        match self {
+            LotSelection::Matured => lot.unlocks_at.is_expired(block),
+            LotSelection::Unbondable => lot.lock_tier.is_none() || lot.unlocks_at.is_expired(block),
+            LotSelection::MaturedFirst | LotSelection::OldestFirst => true,
+        }
+    }
+}
+
+// lots of an owner passing `filter`, oldest first, until they cover `amount`. Returns them with
+// the amount they cover.
+fn cw20_lots_covering(
+    storage: &dyn Storage,
+    owner: &Addr,
+    contract: &Addr,
+    amount: Uint128,
+    filter: impl Fn(&Cw20Lot) -> bool,
+) -> StdResult<(Vec<Cw20Lot>, Uint128)> {
+    let mut lots = vec![];
+    let mut covered = Uint128::zero();
+    for lot in CW20_LOTS.prefix((owner, contract)).range(storage, None, None, Order::Ascending) {
+        if covered >= amount {
+            break;
+        }
+        let (_, lot) = lot?;
+        if filter(&lot) {
+            covered += lot.amount;
+            lots.push(lot);
+        }
+    }
+    Ok((lots, covered))
+}
+
+// takes `amount` out of the lots of an owner, reading no more lots than needed. Fails when the
+// selected lots do not cover the amount. The parts taken keep the lock of their lot.
+fn take_cw20_lots(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    contract: &Addr,
+    amount: Uint128,
+    selection: LotSelection,
+) -> Result<Vec<Cw20Lot>, ContractError> {
+    let (lots, covered) = match selection {
+        LotSelection::MaturedFirst => {
+            let (mut lots, covered) = cw20_lots_covering(storage, owner, contract, amount, |lot| lot.unlocks_at.is_expired(block))?;
+            let (locked, rest) = cw20_lots_covering(storage, owner, contract, amount - covered, |lot| !lot.unlocks_at.is_expired(block))?;
+            lots.extend(locked);
+            (lots, covered + rest)
+        }
+        _ => cw20_lots_covering(storage, owner, contract, amount, |lot| selection.can_take(lot, block))?,
+    };
+    if covered < amount {
+        return Err(ContractError::StakeDurationNotPassed {});
+    }
+
+    let mut remaining = amount;
+    let mut taken = vec![];
+    for mut lot in lots {
+        if remaining.is_zero() {
+            break;
+        }
+        let part = lot.amount.min(remaining);
+        remaining -= part;
+        if part == lot.amount {
+            CW20_LOTS.remove(storage, (owner, contract, lot.id));
+            taken.push(lot);
+        } else {
+            //the weight is split in proportion, rounding stays with the lot
+            let weight = lot.weight.multiply_ratio(part, lot.amount);
+            lot.amount -= part;
+            lot.weight -= weight;
+            CW20_LOTS.save(storage, (owner, contract, lot.id), &lot)?;
+            taken.push(Cw20Lot { amount: part, weight, ..lot });
+        }
+    }
+    Ok(taken)
+}
+
+// part of the lock of a lot still to run at `block`, from one at deposit down to zero at unlock
+fn remaining_lock_share(lot: &Cw20Lot, block: &BlockInfo) -> Decimal {
+    let (left, total) = match lot.unlocks_at {
+        Expiration::AtHeight(height) => (
+            height.saturating_sub(block.height),
+            height.saturating_sub(lot.deposited_at.height),
+        ),
+        Expiration::AtTime(time) => (
+            time.nanos().saturating_sub(block.time.nanos()),
+            time.nanos().saturating_sub(lot.deposited_at.time.nanos()),
+        ),
+        Expiration::Never {} => return Decimal::one(),
+    };
+    if total == 0 {
+        return Decimal::zero();
+    }
+    Decimal::from_ratio(left.min(total), total)
+}
+
+pub fn execute_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    lock_duration: Option<Duration>,
+) -> Result<Response, ContractError> {
+    let mut config = assert_admin(deps.as_ref(), &info)?;
+
+    if let Some(lock_duration) = lock_duration {
+        config.lock_duration = lock_duration;
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("execute", "update_config"))
+}
+
+pub fn execute_set_early_withdraw_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    policy: Option<EarlyWithdrawPolicy>,
+) -> Result<Response, ContractError> {
+    let mut config = assert_admin(deps.as_ref(), &info)?;
+
+    config.early_withdraw = match policy {
+        Some(policy) => {
+            if policy.max_penalty > Decimal::one() {
+                return Err(ContractError::InvalidPenalty {});
+            }
+            let destination = match policy.destination {
+                PenaltyDestination::Treasury { address } => {
+                    PenaltyDestination::Treasury { address: deps.api.addr_validate(address.as_str())? }
+                }
+                PenaltyDestination::Stakers {} => PenaltyDestination::Stakers {},
+            };
+            Some(EarlyWithdrawPolicy { max_penalty: policy.max_penalty, destination })
+        }
+        None => None,
+    };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "set_early_withdraw_policy")
+        .add_attribute("enabled", config.early_withdraw.is_some().to_string()))
+}
+
+pub fn execute_set_unbonding_period(
+    deps: DepsMut,
+    info: MessageInfo,
+    period: Option<Duration>,
+) -> Result<Response, ContractError> {
+    let mut config = assert_admin(deps.as_ref(), &info)?;
+    config.unbonding_period = period;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "set_unbonding_period")
+        .add_attribute("period", period.map_or("none".to_string(), |period| period.to_string())))
+}
+
+pub fn execute_set_cw20_lock_duration(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    lock_duration: Option<Duration>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    match lock_duration {
+        Some(lock_duration) => CW20_LOCK_DURATIONS.save(deps.storage, &contract, &lock_duration)?,
+        None => CW20_LOCK_DURATIONS.remove(deps.storage, &contract),
+    }
+
+    Ok(Response::new()
+        .add_attribute("execute", "set_cw20_lock_duration")
+        .add_attribute("contract", contract))
+}
+
+pub fn execute_set_lock_tier(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+    duration: Duration,
+    reward_multiplier: Decimal,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+    if name.is_empty() {
+        return Err(ContractError::InvalidLockTierName {});
+    }
+    let max = Decimal::from_ratio(MAX_REWARD_MULTIPLIER, 1u64);
+    if reward_multiplier.is_zero() || reward_multiplier > max {
+        return Err(ContractError::InvalidRewardMultiplier { max });
+    }
+    LOCK_TIERS.save(deps.storage, &name, &LockTier { duration, reward_multiplier })?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "set_lock_tier")
+        .add_attribute("name", name)
+        .add_attribute("duration", duration.to_string())
+        .add_attribute("reward_multiplier", reward_multiplier.to_string()))
+}
+
+pub fn execute_remove_lock_tier(deps: DepsMut, info: MessageInfo, name: String) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+    if !LOCK_TIERS.has(deps.storage, &name) {
+        return Err(ContractError::UnknownLockTier { name });
+    }
+    LOCK_TIERS.remove(deps.storage, &name);
+
+    Ok(Response::new()
+        .add_attribute("execute", "remove_lock_tier")
+        .add_attribute("name", name))
+}
+
+pub fn execute_propose_new_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+    admin: String,
+) -> Result<Response, ContractError> {
+    let mut config = assert_admin(deps.as_ref(), &info)?;
+
+    let admin = deps.api.addr_validate(&admin)?;
+    config.pending_admin = Some(admin.clone());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "propose_new_admin")
+        .add_attribute("pending_admin", admin))
+}
+
+pub fn execute_accept_admin(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.pending_admin.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    config.admin = info.sender;
+    config.pending_admin = None;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "accept_admin")
+        .add_attribute("admin", config.admin))
+}
+
+pub fn execute_add_allowed_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    min_deposit: Option<Uint128>,
+    max_deposit: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    ALLOWED_CW20S.save(deps.storage, &contract, &TokenLimits { min_deposit, max_deposit })?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "add_allowed_token")
+        .add_attribute("contract", contract))
+}
+
+pub fn execute_remove_allowed_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    ALLOWED_CW20S.remove(deps.storage, &contract);
+
+    Ok(Response::new()
+        .add_attribute("execute", "remove_allowed_token")
+        .add_attribute("contract", contract))
+}
+
+pub fn execute_add_allowed_cw1155(deps: DepsMut, info: MessageInfo, contract: String) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    ALLOWED_CW1155S.save(deps.storage, &contract, &Empty {})?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "add_allowed_cw1155")
+        .add_attribute("contract", contract))
+}
+
+pub fn execute_remove_allowed_cw1155(deps: DepsMut, info: MessageInfo, contract: String) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    let contract = deps.api.addr_validate(&contract)?;
+    ALLOWED_CW1155S.remove(deps.storage, &contract);
+
+    Ok(Response::new()
+        .add_attribute("execute", "remove_allowed_cw1155")
+        .add_attribute("contract", contract))
+}
+
+pub fn execute_add_allowed_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    limits: DenomLimits,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    if denom.is_empty() {
+        return Err(ContractError::InvalidCoin {});
+    }
+    let kind = denoms::classify(&denom)?;
+    DENOM_KINDS.save(deps.storage, &denom, &kind)?;
+    ALLOWED_DENOMS.save(deps.storage, &denom, &limits)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "add_allowed_denom")
+        .add_attribute("denom", denom))
+}
+
+pub fn execute_remove_allowed_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    ALLOWED_DENOMS.remove(deps.storage, &denom);
+
+    Ok(Response::new()
+        .add_attribute("execute", "remove_allowed_denom")
+        .add_attribute("denom", denom))
+}
+
+pub fn execute_set_ibc_trace(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: String,
+    trace: Option<IbcTrace>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+    if !matches!(denoms::classify(&denom)?, DenomKind::Ibc { .. }) {
+        return Err(ContractError::NotIbcDenom { denom });
+    }
+    match &trace {
+        Some(trace) => IBC_TRACES.save(deps.storage, &denom, trace)?,
+        None => IBC_TRACES.remove(deps.storage, &denom),
+    }
+
+    let mut res = Response::new()
+        .add_attribute("execute", "set_ibc_trace")
+        .add_attribute("denom", denom);
+    if let Some(trace) = trace {
+        res = res
+            .add_attribute("path", trace.path)
+            .add_attribute("base_denom", trace.base_denom);
+    }
+    Ok(res)
+}
+
+pub fn execute_set_pause(
+    deps: DepsMut,
+    info: MessageInfo,
+    native_deposit: Option<bool>,
+    native_withdraw: Option<bool>,
+    cw20_deposit: Option<bool>,
+    cw20_withdraw: Option<bool>,
+) -> Result<Response, ContractError> {
+    assert_admin(deps.as_ref(), &info)?;
+
+    let mut paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    paused.native_deposit = native_deposit.unwrap_or(paused.native_deposit);
+    paused.native_withdraw = native_withdraw.unwrap_or(paused.native_withdraw);
+    paused.cw20_deposit = cw20_deposit.unwrap_or(paused.cw20_deposit);
+    paused.cw20_withdraw = cw20_withdraw.unwrap_or(paused.cw20_withdraw);
+    PAUSED.save(deps.storage, &paused)?;
+
+    Ok(Response::new()
+        .add_attribute("execute", "set_pause")
+        .add_attribute("native_deposit", paused.native_deposit.to_string())
+        .add_attribute("native_withdraw", paused.native_withdraw.to_string())
+        .add_attribute("cw20_deposit", paused.cw20_deposit.to_string())
+        .add_attribute("cw20_withdraw", paused.cw20_withdraw.to_string()))
+}
+
+fn assert_not_paused(deps: Deps, operation: Operation) -> Result<(), ContractError> {
+    let paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    if paused.is_paused(operation) {
+        return Err(ContractError::Paused { operation: operation.as_str().to_string() });
+    }
+    Ok(())
+}
+
+// loads the config, failing unless the sender is the admin
+fn assert_admin(deps: Deps, info: &MessageInfo) -> Result<Config, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(config)
+}
+
+// lock duration for new deposits of a cw20 contract, the override wins over the default
+fn cw20_lock_duration(deps: Deps, contract: &Addr) -> StdResult<Duration> {
+    match CW20_LOCK_DURATIONS.may_load(deps.storage, contract)? {
+        Some(lock_duration) => Ok(lock_duration),
+        None => Ok(CONFIG.load(deps.storage)?.lock_duration),
+    }
+}
+
+
+fn query_balances(deps: Deps, address: String, start_after: Option<AssetInfo>, limit: Option<u32>) -> StdResult<BalancesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let address = deps.api.addr_validate(&address)?;
+    let start_after = start_after
+        .map(|asset| validate_asset(deps, asset))
+        .transpose()
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let start = start_after.as_ref().map(|asset| Bound::exclusive(balance_key(asset)));
+
+    let balances: StdResult<Vec<_>> = balances()
+        .sub_prefix(&address)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, balance)| balance))
+        .collect();
+    Ok(BalancesResponse { balances: balances? })
+}
+
+pub fn query_deposits(deps: Deps, address:String, start_after: Option<String>, limit: Option<u32>) -> StdResult<DepositResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let address = deps.api.addr_validate(&address)?;
+    let res: StdResult<Vec<_>> = balances()
+        .prefix((&address, NATIVE_KIND))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(denom, balance)| {
+                let deposit = Deposits {
+                    deposit_count: balance.deposit_count,
+                    withdraw_count: balance.withdraw_count,
+                    last_deposit: balance.last_deposit,
+                    last_withdraw: balance.last_withdraw,
+                    owner: balance.owner,
+                    coins: coin(balance.amount.u128(), &denom),
+                };
+                (denom, deposit)
+            })
+        })
+        .collect();
+    let deposits = res?;
+    Ok(DepositResponse { deposits })
+}
+
+fn query_cw20_deposits(deps: Deps, address: String, start_after: Option<String>, limit: Option<u32>) -> StdResult<Cw20DepositResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let address = deps.api.addr_validate(&address)?;
+    let start_after = maybe_addr(deps.api, start_after)?;
+    let start = start_after.as_ref().map(|contract| Bound::exclusive(contract.as_str()));
+
+    let res: StdResult<Vec<_>> = balances()
+        .prefix((&address, CW20_KIND))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(contract, balance)| {
+                let contract = Addr::unchecked(contract);
+                let deposit = Cw20Deposits {
+                    deposit_count: balance.deposit_count,
+                    withdraw_count: balance.withdraw_count,
+                    last_deposit: balance.last_deposit,
+                    last_withdraw: balance.last_withdraw,
+                    owner: balance.owner,
+                    contract: contract.clone(),
+                    amount: balance.amount,
+                };
+                (contract, deposit)
+            })
+        })
+        .collect();
+    let deposits = res?;
+    Ok(Cw20DepositResponse { deposits })
+}
+
+fn query_cw20_balance(deps: Deps, env: Env, address: String, contract: String) -> StdResult<Cw20BalanceResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    let total = balance_of(deps.storage, &address, &cw20_asset(&contract))?;
+    let withdrawable = withdrawable_cw20(deps, &env, &address, &contract)?;
+    Ok(Cw20BalanceResponse { locked: total - withdrawable, withdrawable })
+}
+
+fn query_cw20_lots(
+    deps: Deps,
+    address: String,
+    contract: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Cw20LotsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let address = deps.api.addr_validate(&address)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    let start = start_after.map(Bound::exclusive);
+    let lots: StdResult<Vec<_>> = CW20_LOTS
+        .prefix((&address, &contract))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|lot| lot.map(|(_, lot)| lot))
+        .collect();
+    Ok(Cw20LotsResponse { lots: lots? })
+}
+
+fn query_cw1155_balance(deps: Deps, env: Env, address: String, contract: String, token_id: String) -> StdResult<Cw1155BalanceResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    let total = CW1155_BALANCES
+        .may_load(deps.storage, (&address, &contract, &token_id))?
+        .unwrap_or_default();
+    let withdrawable = withdrawable_cw1155(deps.storage, &env.block, &address, &contract, &token_id)?;
+    Ok(Cw1155BalanceResponse { locked: total - withdrawable, withdrawable })
+}
+
+fn query_cw1155_lots(
+    deps: Deps,
+    address: String,
+    contract: String,
+    token_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Cw1155LotsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let address = deps.api.addr_validate(&address)?;
+    let contract = deps.api.addr_validate(&contract)?;
+    let start = start_after.map(Bound::exclusive);
+    let lots: StdResult<Vec<_>> = CW1155_LOTS
+        .prefix((&address, (&contract, &token_id)))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|lot| lot.map(|(_, lot)| lot))
+        .collect();
+    Ok(Cw1155LotsResponse { lots: lots? })
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        admin: config.admin,
+        pending_admin: config.pending_admin,
+        lock_duration: config.lock_duration,
+        early_withdraw: config.early_withdraw,
+        unbonding_period: config.unbonding_period,
+    })
+}
+
+fn query_pause_status(deps: Deps) -> StdResult<PauseState> {
+    Ok(PAUSED.may_load(deps.storage)?.unwrap_or_default())
+}
+
+fn query_all_depositors(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<DepositorsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = maybe_addr(deps.api, start_after)?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let depositors: StdResult<Vec<_>> = DEPOSITORS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+    Ok(DepositorsResponse { depositors: depositors? })
+}
+
+fn query_denom_total(deps: Deps, denom: String) -> StdResult<DenomTotalResponse> {
+    let total = DENOM_TOTALS.may_load(deps.storage, &denom)?.unwrap_or_default();
+    Ok(DenomTotalResponse { denom, total })
+}
+
+fn query_cw20_total(deps: Deps, contract: String) -> StdResult<Cw20TotalResponse> {
+    let contract = deps.api.addr_validate(&contract)?;
+    let total = CW20_TOTALS.may_load(deps.storage, &contract)?.unwrap_or_default();
+    let unbonding = CW20_UNBONDING_TOTALS.may_load(deps.storage, &contract)?.unwrap_or_default();
+    let penalties = UNCLAIMED_PENALTIES.may_load(deps.storage, &contract)?.unwrap_or_default();
+    Ok(Cw20TotalResponse { contract, total, unbonding, penalties })
+}
+
+fn query_all_denom_totals(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<AllDenomTotalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let totals: StdResult<Vec<_>> = DENOM_TOTALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(denom, total)| DenomTotalResponse { denom, total }))
+        .collect();
+    Ok(AllDenomTotalsResponse { totals: totals? })
+}
+
+fn query_denom_holders(deps: Deps, denom: String, start_after: Option<Holder>, limit: Option<u32>) -> StdResult<HoldersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    // descending, so the previous page ends at the upper bound
+    let end = start_after.map(|holder| {
+        Bound::exclusive((holder.amount.u128(), (holder.address, NATIVE_KIND.to_string(), denom.clone())))
+    });
+
+    let holders: StdResult<Vec<_>> = balances()
+        .idx
+        .asset
+        .sub_prefix(asset_key(&AssetInfo::Native { denom: denom.clone() }))
+        .range(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|((address, _, _), balance)| Holder { address, amount: balance.amount }))
+        .collect();
+    Ok(HoldersResponse { holders: holders? })
+}
+
+fn query_cw20_holders(deps: Deps, contract: String, start_after: Option<Holder>, limit: Option<u32>) -> StdResult<HoldersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    // descending, so the previous page ends at the upper bound
+    let contract = deps.api.addr_validate(&contract)?;
+    let end = start_after.map(|holder| {
+        Bound::exclusive((holder.amount.u128(), (holder.address, CW20_KIND.to_string(), contract.to_string())))
+    });
+
+    let holders: StdResult<Vec<_>> = balances()
+        .idx
+        .asset
+        .sub_prefix(asset_key(&cw20_asset(&contract)))
+        .range(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|((address, _, _), balance)| Holder { address, amount: balance.amount }))
+        .collect();
+    Ok(HoldersResponse { holders: holders? })
+}
+
+fn query_allowed_tokens(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<AllowedTokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = maybe_addr(deps.api, start_after)?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let tokens: StdResult<Vec<_>> = ALLOWED_CW20S
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(contract, limits)| AllowedToken {
+                contract,
+                min_deposit: limits.min_deposit,
+                max_deposit: limits.max_deposit,
+            })
+        })
+        .collect();
+    Ok(AllowedTokensResponse { tokens: tokens? })
+}
+
+fn query_allowed_cw1155s(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<AllowedCw1155sResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = maybe_addr(deps.api, start_after)?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let contracts: StdResult<Vec<_>> = ALLOWED_CW1155S
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+    Ok(AllowedCw1155sResponse { contracts: contracts? })
+}
+
+fn query_allowed_denoms(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<AllowedDenomsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let denoms: StdResult<Vec<_>> = ALLOWED_DENOMS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(denom, limits)| AllowedDenom {
+                denom,
+                min_deposit: limits.min_deposit,
+                max_user_balance: limits.max_user_balance,
+                global_cap: limits.global_cap,
+            })
+        })
+        .collect();
+    Ok(AllowedDenomsResponse { denoms: denoms? })
+}
+
+fn query_lock_tiers(deps: Deps, start_after: Option<String>, limit: Option<u32>) -> StdResult<LockTiersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let tiers: StdResult<Vec<_>> = LOCK_TIERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(name, tier)| LockTierInfo {
+                name,
+                duration: tier.duration,
+                reward_multiplier: tier.reward_multiplier,
+            })
+        })
+        .collect();
+    Ok(LockTiersResponse { tiers: tiers? })
+}
+
+// denoms that were never allowed are classified on the fly
+fn query_denom_info(deps: Deps, denom: String) -> StdResult<DenomInfoResponse> {
+    let kind = match DENOM_KINDS.may_load(deps.storage, &denom)? {
+        Some(kind) => kind,
+        None => denoms::classify(&denom).map_err(|err| StdError::generic_err(err.to_string()))?,
+    };
+    let trace = IBC_TRACES.may_load(deps.storage, &denom)?;
+    Ok(DenomInfoResponse { denom, kind, trace })
+}
+
+fn query_effective_lock_duration(deps: Deps, contract: String) -> StdResult<LockDurationResponse> {
+    let contract = deps.api.addr_validate(&contract)?;
+    let overridden = CW20_LOCK_DURATIONS.has(deps.storage, &contract);
+    let lock_duration = cw20_lock_duration(deps, &contract)?;
+    Ok(LockDurationResponse { contract, lock_duration, overridden })
+}
+
+fn query_allowance(deps: Deps, owner: String, spender: String, asset: AssetInfo) -> StdResult<AllowanceResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let spender = deps.api.addr_validate(&spender)?;
+    let asset = validate_asset(deps, asset).map_err(|err| StdError::generic_err(err.to_string()))?;
+    let allowance = ALLOWANCES.may_load(deps.storage, (&owner, &spender, &asset_key(&asset)))?;
+    Ok(match allowance {
+        Some(allowance) => AllowanceResponse { allowance: allowance.amount, expires: allowance.expires },
+        None => AllowanceResponse { allowance: Uint128::zero(), expires: Expiration::Never {} },
+    })
+}
+
+fn query_all_allowances(
+    deps: Deps,
+    owner: String,
+    start_after: Option<AllowanceKey>,
+    limit: Option<u32>,
+) -> StdResult<AllAllowancesResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some(key) => {
+            let asset = validate_asset(deps, key.asset).map_err(|err| StdError::generic_err(err.to_string()))?;
+            Some((deps.api.addr_validate(&key.spender)?, asset_key(&asset)))
+        }
+        None => None,
+    };
+    let start = start_after.as_ref().map(|(spender, key)| Bound::exclusive((spender, key.as_str())));
+
+    let allowances: StdResult<Vec<_>> = ALLOWANCES
+        .sub_prefix(&owner)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|((spender, _), allowance)| AllowanceInfo {
+                spender,
+                asset: allowance.asset,
+                allowance: allowance.amount,
+                expires: allowance.expires,
+            })
+        })
+        .collect();
+    Ok(AllAllowancesResponse { allowances: allowances? })
+}
+
+fn query_reward_pool(deps: Deps, env: Env, contract: String) -> StdResult<RewardPool> {
+    let contract = deps.api.addr_validate(&contract)?;
+    let mut pool = REWARD_POOLS.load(deps.storage, &contract)?;
+    let total_staked = rewards::total_weight(deps.storage, &contract)?;
+    rewards::accrue(&mut pool, env.block.time, total_staked)?;
+    Ok(pool)
+}
+
+fn query_pending_rewards(deps: Deps, env: Env, address: String) -> StdResult<PendingRewardsResponse> {
+    let owner = deps.api.addr_validate(&address)?;
+    let mut rewards = vec![];
+    for contract in rewarded_contracts(deps.storage, &owner)? {
+        for (reward, amount) in rewards::pending(deps.storage, &env.block, &owner, &contract)? {
+            rewards.push(PendingReward { contract: contract.clone(), reward, amount });
+        }
+    }
+    Ok(PendingRewardsResponse { rewards })
+}
+
+fn query_claims(deps: Deps, address: String, start_after: Option<u64>, limit: Option<u32>) -> StdResult<ClaimsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let address = deps.api.addr_validate(&address)?;
+    let start = start_after.map(Bound::exclusive);
+    let claims: StdResult<Vec<_>> = CW20_CLAIMS
+        .prefix(&address)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|claim| claim.map(|(_, claim)| claim))
+        .collect();
+    Ok(ClaimsResponse { claims: claims? })
+}
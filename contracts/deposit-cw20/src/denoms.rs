@@ -0,0 +1,26 @@
+use crate::error::ContractError;
+use crate::state::DenomKind;
+
+const IBC_PREFIX: &str = "ibc/";
+const FACTORY_PREFIX: &str = "factory/";
+
+/// Tells native, ibc and token factory denoms apart. Ibc denoms need the 64 hex characters of
+/// their trace hash, token factory denoms a creator and a subdenom.
+pub fn classify(denom: &str) -> Result<DenomKind, ContractError> {
+    if let Some(hash) = denom.strip_prefix(IBC_PREFIX) {
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ContractError::InvalidDenom { denom: denom.to_string() });
+        }
+        return Ok(DenomKind::Ibc { hash: hash.to_uppercase() });
+    }
+    if let Some(rest) = denom.strip_prefix(FACTORY_PREFIX) {
+        return match rest.split_once('/') {
+            Some((creator, subdenom)) if !creator.is_empty() && !subdenom.is_empty() => Ok(DenomKind::TokenFactory {
+                creator: creator.to_string(),
+                subdenom: subdenom.to_string(),
+            }),
+            _ => Err(ContractError::InvalidDenom { denom: denom.to_string() }),
+        };
+    }
+    Ok(DenomKind::Native {})
+}
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,9 +6,33 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
 
+    #[error("Admin is required when migrating from 0.1.0")]
+    MissingAdmin {},
+
+    #[error("No funds sent")]
+    NoFunds {},
+
+    #[error("Cannot deposit zero {denom}")]
+    ZeroAmount { denom: String },
+
+    #[error("Cannot migrate from {found}, expected {expected}")]
+    WrongContract { expected: String, found: String },
+
+    #[error("Cannot migrate from {previous} to the older {current}")]
+    CannotDowngrade { previous: String, current: String },
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("{operation} is paused")]
+    Paused { operation: String },
+
     #[error("Stake duration has not passed")]
     StakeDurationNotPassed{},
 
@@ -19,7 +43,106 @@ pub enum ContractError {
     #[error("Invalid Coin")]
     InvalidCoin {},
 
+    #[error("Cw20 contract {contract} is not allowed")]
+    TokenNotAllowed { contract: String },
+
+    #[error("Cw1155 contract {contract} is not allowed")]
+    Cw1155NotAllowed { contract: String },
+
+    #[error("Denom {denom} is not allowed")]
+    DenomNotAllowed { denom: String },
+
+    #[error("Deposit would take the user balance of {denom} above {cap}")]
+    UserBalanceCapExceeded { denom: String, cap: Uint128 },
+
+    #[error("Deposit would take the contract balance of {denom} above {cap}")]
+    GlobalCapExceeded { denom: String, cap: Uint128 },
+
+    #[error("Deposit of {asset} is below the minimum of {min}")]
+    DepositTooSmall { asset: String, min: Uint128 },
+
+    #[error("Deposit of {asset} is above the maximum of {max}")]
+    DepositTooLarge { asset: String, max: Uint128 },
+
     #[error("User does not have coins from this cw20 to withdraw")]
     NoCw20ToWithdraw {},
 
+    #[error("User does not have tokens of this cw1155 id to withdraw")]
+    NoCw1155ToWithdraw {},
+
+    #[error("Insufficient funds: requested {requested}, available {available}")]
+    InsufficientFunds { requested: Uint128, available: Uint128 },
+
+    #[error("No deposit for denom {denom}")]
+    NoDepositForDenom { denom: String },
+
+    #[error("Nothing to withdraw")]
+    NothingToWithdraw {},
+
+    #[error("Cannot transfer to self")]
+    CannotTransferToSelf {},
+
+    #[error("Cannot set allowance to own account")]
+    CannotSetOwnAccount {},
+
+    #[error("Invalid expiration value")]
+    InvalidExpiration {},
+
+    #[error("Allowance is expired")]
+    Expired {},
+
+    #[error("No allowance for this account")]
+    NoAllowance {},
+
+    #[error("Insufficient allowance: requested {requested}, allowed {allowance}")]
+    InsufficientAllowance { requested: Uint128, allowance: Uint128 },
+
+    #[error("No reward pool for {contract}")]
+    NoRewardPool { contract: String },
+
+    #[error("The reward pool pays {expected}")]
+    WrongRewardAsset { expected: String },
+
+    #[error("No rewards to claim")]
+    NoRewardsToClaim {},
+
+    #[error("Early withdraws are disabled")]
+    EarlyWithdrawDisabled {},
+
+    #[error("Penalty cannot be more than 100%")]
+    InvalidPenalty {},
+
+    #[error("Unbonding is disabled")]
+    UnbondingDisabled {},
+
+    #[error("Cw20 has to be unbonded before it is withdrawn")]
+    UnbondingRequired {},
+
+    #[error("No released claims")]
+    NothingToClaim {},
+
+    #[error("Unknown lock tier {name}")]
+    UnknownLockTier { name: String },
+
+    #[error("Lock tier name cannot be empty")]
+    InvalidLockTierName {},
+
+    #[error("Reward multiplier has to be above zero and at most {max}")]
+    InvalidRewardMultiplier { max: Decimal },
+
+    #[error("Cannot hold more than {max} lots of one asset")]
+    TooManyLots { max: usize },
+
+    #[error("Memo cannot be longer than {max} bytes")]
+    MemoTooLong { max: usize },
+
+    #[error("Invalid denom {denom}")]
+    InvalidDenom { denom: String },
+
+    #[error("{denom} is not an ibc denom")]
+    NotIbcDenom { denom: String },
+
+    #[error("Exactly one of asset and denom has to be set")]
+    InvalidAsset {},
+
 }
@@ -22,7 +22,7 @@ impl DepositContract {
         Ok(WasmMsg::Execute {
             contract_addr: self.addr().into(),
             msg,
-            funds: funds,
+            funds,
         }
         .into())
     }
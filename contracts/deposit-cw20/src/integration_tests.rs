@@ -1,13 +1,19 @@
 #[cfg(test)]
 mod tests {
     use crate::helpers::DepositContract;
-    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, Cw20HookMsg, Cw20DepositResponse, DepositResponse};
-    use cosmwasm_std::{Addr, Coin, Empty, Uint128, to_binary, coin, WasmMsg};
+    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, Cw20HookMsg, Cw20DepositResponse, DepositResponse, ConfigResponse, LockDurationResponse, Cw20BalanceResponse, Cw20LotsResponse, AllowedTokensResponse, AllowedDenomsResponse, MigrateMsg, DepositorsResponse, DenomTotalResponse, Cw20TotalResponse, AllDenomTotalsResponse, Holder, HoldersResponse, AssetInfo, AllowanceResponse, AllowanceKey, AllAllowancesResponse, PendingRewardsResponse, ClaimsResponse, LockTiersResponse, DenomInfoResponse, BalancesResponse, Cw1155ReceiveMsg, Cw1155BatchReceiveMsg, Cw1155HookMsg, Cw1155BalanceResponse, Cw1155LotsResponse, AllowedCw1155sResponse};
+    use crate::state::{Cw20Lot, DenomKind, EarlyWithdrawPolicy, IbcTrace, PauseState, PenaltyDestination, RewardPool};
+    use crate::ContractError;
+    use cosmwasm_std::{Addr, Binary, Coin, Decimal, Deps, DepsMut, Empty, Env, Event, MessageInfo, Response, StdResult, Uint128, to_binary, coin, WasmMsg};
     use cw20::{Cw20Contract, Cw20Coin, BalanceResponse};
+    use cw_storage_plus::Map;
+    use cw_utils::{Duration, Expiration};
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
     use cw20_base::msg::ExecuteMsg as Cw20ExecuteMsg;
     use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
     use cw20_base::msg::QueryMsg as Cw20QueryMsg;
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use cw_multi_test::{App, AppBuilder, BankSudo, Contract, ContractWrapper, Executor, SudoMsg};
 
     use cw20_example::{self};
 
@@ -16,10 +22,66 @@ mod tests {
             crate::contract::execute,
             crate::contract::instantiate,
             crate::contract::query,
-        );
+        )
+        .with_migrate(crate::contract::migrate);
         Box::new(contract)
     }
 
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    struct LegacyInstantiateMsg {
+        version: String,
+        cw20: String,
+    }
+
+    // the 0.1.0 cw20 deposit, with one stake time for the whole balance
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    struct LegacyCw20Deposits {
+        count: i32,
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        stake_time: Expiration,
+    }
+
+    // the native deposit up to 0.4.0, with a count that went down on withdraws
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    struct LegacyDeposits {
+        count: i32,
+        owner: String,
+        coins: Coin,
+    }
+
+    // stands in for a deployed 0.1.0 contract: USER holds 500 of the cw20 and 400 denom, and
+    // an empty record is left from a full withdraw of the other denom
+    fn legacy_instantiate(deps: DepsMut, env: Env, _info: MessageInfo, msg: LegacyInstantiateMsg) -> StdResult<Response> {
+        cw2::set_contract_version(deps.storage, "deposit-cw20-example", msg.version)?;
+        let legacy = LegacyCw20Deposits {
+            count: 1,
+            owner: USER.to_string(),
+            contract: msg.cw20.clone(),
+            amount: Uint128::new(500),
+            stake_time: Expiration::AtHeight(env.block.height + 20),
+        };
+        Map::new("cw20deposits").save(deps.storage, (USER, msg.cw20.as_str()), &legacy)?;
+        let deposit = LegacyDeposits { count: 1, owner: USER.to_string(), coins: coin(400, NATIVE_DENOM) };
+        Map::new("deposits").save(deps.storage, (USER, NATIVE_DENOM), &deposit)?;
+        let deposit = LegacyDeposits { count: 0, owner: USER.to_string(), coins: coin(0, OTHER_DENOM) };
+        Map::new("deposits").save(deps.storage, (USER, OTHER_DENOM), &deposit)?;
+        Ok(Response::default())
+    }
+
+    fn legacy_execute(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn legacy_query(_deps: Deps, _env: Env, _msg: Empty) -> StdResult<Binary> {
+        Ok(Binary::default())
+    }
+
+    pub fn contract_legacy_deposit() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(legacy_execute, legacy_instantiate, legacy_query))
+    }
+
     pub fn contract_cw20() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
             cw20_example::contract::execute,
@@ -29,9 +91,102 @@ mod tests {
         Box::new(contract)
     }
 
+    // the parts of the cw1155 spec the deposit contract relies on, plus minting
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum MockCw1155ExecuteMsg {
+        Mint { to: String, token_id: String, value: Uint128 },
+        SendFrom { from: String, to: String, token_id: String, value: Uint128, msg: Option<Binary> },
+        BatchSendFrom { from: String, to: String, batch: Vec<(String, Uint128)>, msg: Option<Binary> },
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum MockCw1155QueryMsg {
+        Balance { owner: String, token_id: String },
+    }
+
+    // what the cw1155 spec sends to a receiving contract, independent of our `ExecuteMsg`
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum Cw1155ReceiverExecuteMsg {
+        Receive(Cw1155ReceiveMsg),
+        BatchReceive(Cw1155BatchReceiveMsg),
+    }
+
+    const MOCK_CW1155_BALANCES: Map<(&str, &str), Uint128> = Map::new("balances");
+
+    fn mock_cw1155_move(deps: DepsMut, from: Option<&str>, to: &str, token_id: &str, value: Uint128) -> StdResult<()> {
+        if let Some(from) = from {
+            let balance = MOCK_CW1155_BALANCES.may_load(deps.storage, (from, token_id))?.unwrap_or_default();
+            MOCK_CW1155_BALANCES.save(deps.storage, (from, token_id), &balance.checked_sub(value)?)?;
+        }
+        let balance = MOCK_CW1155_BALANCES.may_load(deps.storage, (to, token_id))?.unwrap_or_default();
+        MOCK_CW1155_BALANCES.save(deps.storage, (to, token_id), &balance.checked_add(value)?)?;
+        Ok(())
+    }
+
+    fn mock_cw1155_instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+        Ok(Response::default())
+    }
+
+    // stands in for a cw1155 contract, without approvals: anyone can move anyone's tokens
+    fn mock_cw1155_execute(mut deps: DepsMut, _env: Env, info: MessageInfo, msg: MockCw1155ExecuteMsg) -> StdResult<Response> {
+        match msg {
+            MockCw1155ExecuteMsg::Mint { to, token_id, value } => {
+                mock_cw1155_move(deps, None, &to, &token_id, value)?;
+                Ok(Response::default())
+            }
+            MockCw1155ExecuteMsg::SendFrom { from, to, token_id, value, msg } => {
+                mock_cw1155_move(deps, Some(&from), &to, &token_id, value)?;
+                let mut res = Response::default();
+                if let Some(msg) = msg {
+                    let receive = Cw1155ReceiverExecuteMsg::Receive(Cw1155ReceiveMsg {
+                        operator: info.sender.into_string(),
+                        from: Some(from),
+                        token_id,
+                        amount: value,
+                        msg,
+                    });
+                    res = res.add_message(WasmMsg::Execute { contract_addr: to, msg: to_binary(&receive)?, funds: vec![] });
+                }
+                Ok(res)
+            }
+            MockCw1155ExecuteMsg::BatchSendFrom { from, to, batch, msg } => {
+                for (token_id, value) in &batch {
+                    mock_cw1155_move(deps.branch(), Some(&from), &to, token_id, *value)?;
+                }
+                let mut res = Response::default();
+                if let Some(msg) = msg {
+                    let receive = Cw1155ReceiverExecuteMsg::BatchReceive(Cw1155BatchReceiveMsg {
+                        operator: info.sender.into_string(),
+                        from: Some(from),
+                        batch,
+                        msg,
+                    });
+                    res = res.add_message(WasmMsg::Execute { contract_addr: to, msg: to_binary(&receive)?, funds: vec![] });
+                }
+                Ok(res)
+            }
+        }
+    }
+
+    fn mock_cw1155_query(deps: Deps, _env: Env, msg: MockCw1155QueryMsg) -> StdResult<Binary> {
+        match msg {
+            MockCw1155QueryMsg::Balance { owner, token_id } => {
+                to_binary(&MOCK_CW1155_BALANCES.may_load(deps.storage, (&owner, &token_id))?.unwrap_or_default())
+            }
+        }
+    }
+
+    pub fn contract_mock_cw1155() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(mock_cw1155_execute, mock_cw1155_instantiate, mock_cw1155_query))
+    }
+
     const USER: &str = "juno10c3slrqx3369mfsr9670au22zvq082jaej8ve4";
     const ADMIN: &str = "ADMIN";
     const NATIVE_DENOM: &str = "denom";
+    const OTHER_DENOM: &str = "other";
 
     fn mock_app() -> App {
         AppBuilder::new().build(|router, _, storage| {
@@ -40,10 +195,16 @@ mod tests {
                 .init_balance(
                     storage,
                     &Addr::unchecked(USER),
-                    vec![Coin {
-                        denom: NATIVE_DENOM.to_string(),
-                        amount: Uint128::new(1000),
-                    }],
+                    vec![
+                        Coin {
+                            denom: NATIVE_DENOM.to_string(),
+                            amount: Uint128::new(1000),
+                        },
+                        Coin {
+                            denom: OTHER_DENOM.to_string(),
+                            amount: Uint128::new(1000),
+                        },
+                    ],
                 )
                 .unwrap();
         })
@@ -58,7 +219,7 @@ mod tests {
     }
 
     fn deposit_instantiate(app: &mut App, deposit_id: u64) -> DepositContract {
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { admin: None, lock_duration: Duration::Height(20) };
         let deposit_contract_address = app
             .instantiate_contract(
                 deposit_id,
@@ -88,9 +249,21 @@ mod tests {
     Cw20Contract(cw20_contract_address)
     }
 
+    fn allow_cw20(app: &mut App, deposit_contract: &DepositContract, cw20_contract: &Cw20Contract) {
+        let msg = ExecuteMsg::AddAllowedToken { contract: cw20_contract.addr().to_string(), min_deposit: None, max_deposit: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+    }
+
+    fn allow_denom(app: &mut App, deposit_contract: &DepositContract, denom: &str) {
+        let msg = ExecuteMsg::AddAllowedDenom { denom: denom.to_string(), min_deposit: None, max_user_balance: None, global_cap: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+    }
+
     fn get_deposits(app: &App, deposit_contract: &DepositContract) -> DepositResponse {
         app.wrap()
-            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Deposits { address: USER.to_string() })
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Deposits { address: USER.to_string(), start_after: None, limit: None })
             .unwrap()
     }
 
@@ -100,7 +273,7 @@ mod tests {
 
     fn get_cw20_deposits(app: &App, deposit_contract: &DepositContract) -> Cw20DepositResponse {
         app.wrap()
-            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: USER.to_string() })
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: USER.to_string(), start_after: None, limit: None })
             .unwrap()
     }
 
@@ -111,11 +284,38 @@ mod tests {
             .unwrap()
     }
 
+    fn get_config(app: &App, deposit_contract: &DepositContract) -> ConfigResponse {
+        app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Config {})
+            .unwrap()
+    }
+
+    fn get_cw20_balance_on_deposit(app: &App, deposit_contract: &DepositContract, cw20_contract: &Cw20Contract) -> Cw20BalanceResponse {
+        app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Balance { address: USER.to_string(), contract: cw20_contract.addr().to_string() })
+            .unwrap()
+    }
+
+    fn send_cw20(app: &mut App, cw20_contract: &Cw20Contract, deposit_contract: &DepositContract, amount: u128) {
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(amount), msg: to_binary(&hook_msg).unwrap() };
+        let cosmos_msg = cw20_contract.call(msg).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+    }
+
+    fn advance_blocks(app: &mut App, blocks: u64) {
+        let mut block = app.block_info();
+        block.height += blocks;
+        block.time = block.time.plus_seconds(5 * blocks);
+        app.set_block(block);
+    }
+
 
     #[test]
     fn deposit_native() {
-        let (mut app, deposit_id, cw20_id) = store_code();
+        let (mut app, deposit_id, _cw20_id) = store_code();
         let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
 
         // The Blockchain was setup with an initial balance of (denom, 1000) for USER.
         let balance = get_balance(&app, USER.to_string(), "denom".to_string());
@@ -142,13 +342,14 @@ mod tests {
         let (mut app, deposit_id, cw20_id) = store_code();
         let deposit_contract = deposit_instantiate(&mut app, deposit_id);
         let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
 
         // On instantiation, User gets 10000 of the cw20 tokens on the cw_20 contract.
         let balance = get_cw20_balance(&app, &cw20_contract, USER.to_string());
         println!("1. CW20 Contract- Initial Balance for USER # {:?}", balance);
 
         // The user sends 500 of those tokens to the deposit contract.
-        let hook_msg = Cw20HookMsg::Deposit { };
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
         let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(500u64), msg: to_binary(&hook_msg).unwrap() };
         let cosmos_msg = cw20_contract.call(msg).unwrap();
         app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
@@ -183,13 +384,14 @@ mod tests {
         let (mut app, deposit_id, cw20_id) = store_code();
         let deposit_contract = deposit_instantiate(&mut app, deposit_id);
         let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
 
         let balance = get_cw20_balance(&app, &cw20_contract, USER.to_string());
         println!("1. CW20 Contract- Initial Balance for USER # {:?}", balance);
 
         // 500 cw20 tokens are sent to the deposit contract. 
         // The CW20 contract registers that the deposit contract has the 500 tokens
-        let hook_msg = Cw20HookMsg::Deposit { };
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
         let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(500u64), msg: to_binary(&hook_msg).unwrap() };
         let cosmos_msg = cw20_contract.call(msg).unwrap();
         app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
@@ -208,7 +410,7 @@ mod tests {
         block.height = app.block_info().height.checked_add(20).unwrap();
         app.set_block(block);
 
-        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(500u64)};
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(500u64), recipient: None };
         let execute_msg = WasmMsg::Execute { contract_addr: deposit_contract.addr().to_string(), msg: to_binary(&msg).unwrap(), funds: vec![] };
         app.execute(Addr::unchecked(USER), execute_msg.into()).unwrap();
 
@@ -221,8 +423,1847 @@ mod tests {
         println!("6. CW20 Contract - DEPOSIT CONTRACT balance # {:?}", balance);
 
         let deposits = get_cw20_deposits(&app, &deposit_contract);
-        println!("7. DEPOSIT contract - USER deposits {:?}", deposits.deposits[0]);       
+        println!("7. DEPOSIT contract - USER deposits {:?}", deposits.deposits);
+        assert!(deposits.deposits.is_empty());
+
+    }
+
+    #[test]
+    fn update_config_is_admin_only() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+
+        let config = get_config(&app, &deposit_contract);
+        assert_eq!(config.admin, Addr::unchecked(ADMIN));
+        assert_eq!(config.lock_duration, Duration::Height(20));
+
+        let msg = ExecuteMsg::UpdateConfig { lock_duration: Some(Duration::Time(3600)) };
+        let cosmos_msg = deposit_contract.call(msg.clone(), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+        let config = get_config(&app, &deposit_contract);
+        assert_eq!(config.lock_duration, Duration::Time(3600));
+    }
+
+    #[test]
+    fn cw20_lock_duration_override() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        let msg = ExecuteMsg::SetCw20LockDuration { contract: cw20_contract.addr().to_string(), lock_duration: Some(Duration::Height(50)) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+
+        let lock: LockDurationResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::EffectiveLockDuration { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(lock.lock_duration, Duration::Height(50));
+        assert!(lock.overridden);
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+
+        // the default lock of 20 blocks does not apply to this token anymore
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(500u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg.clone(), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+
+        advance_blocks(&mut app, 30);
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let balance = get_cw20_balance(&app, &cw20_contract, USER.to_string());
+        assert_eq!(Uint128::from(10000u64), balance.balance);
+    }
+
+    #[test]
+    fn cw20_lots_unlock_independently() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        advance_blocks(&mut app, 10);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 300);
+
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots.len(), 2);
+        assert_eq!(lots.lots[0].amount, Uint128::from(500u64));
+        assert_eq!(lots.lots[1].amount, Uint128::from(300u64));
+        let page: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after: Some(lots.lots[0].id), limit: Some(1) })
+            .unwrap();
+        assert_eq!(page.lots, vec![lots.lots[1].clone()]);
+
+        // topping up did not re-lock the first lot
+        advance_blocks(&mut app, 10);
+        let balance = get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract);
+        assert_eq!(balance, Cw20BalanceResponse { locked: Uint128::from(300u64), withdrawable: Uint128::from(500u64) });
+
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(600u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(400u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let balance = get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract);
+        assert_eq!(balance, Cw20BalanceResponse { locked: Uint128::from(300u64), withdrawable: Uint128::from(100u64) });
+
+        // once the second lot matures everything left can be withdrawn
+        advance_blocks(&mut app, 10);
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(400u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let balance = get_cw20_balance(&app, &cw20_contract, USER.to_string());
+        assert_eq!(Uint128::from(10000u64), balance.balance);
+    }
+
+    #[test]
+    fn deposit_native_multiple_coins() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+        allow_denom(&mut app, &deposit_contract, OTHER_DENOM);
+
+        let msg = ExecuteMsg::Deposit { };
+        let cosmos_msg = deposit_contract.call(msg.clone(), vec![coin(400, NATIVE_DENOM), coin(250, OTHER_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let deposits = get_deposits(&app, &deposit_contract);
+        assert_eq!(deposits.deposits.len(), 2);
+        assert_eq!(deposits.deposits[0].1.coins, coin(400, NATIVE_DENOM));
+        assert_eq!(deposits.deposits[1].1.coins, coin(250, OTHER_DENOM));
+
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoFunds {}));
+
+        let page: DepositResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Deposits { address: USER.to_string(), start_after: None, limit: Some(1) })
+            .unwrap();
+        assert_eq!(page.deposits.len(), 1);
+        assert_eq!(page.deposits[0].0, NATIVE_DENOM);
+        let page: DepositResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Deposits { address: USER.to_string(), start_after: Some(NATIVE_DENOM.to_string()), limit: Some(1) })
+            .unwrap();
+        assert_eq!(page.deposits.len(), 1);
+        assert_eq!(page.deposits[0].0, OTHER_DENOM);
+    }
+
+    #[test]
+    fn withdraw_native_errors() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(400, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(500), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        match err.downcast().unwrap() {
+            ContractError::InsufficientFunds { requested, available } => {
+                assert_eq!(requested, Uint128::new(500));
+                assert_eq!(available, Uint128::new(400));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(OTHER_DENOM.to_string()), amount: Uint128::new(100), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoDepositForDenom { denom } if denom == OTHER_DENOM));
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(0), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidCoin {}));
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(400), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(1000));
+    }
+
+    #[test]
+    fn withdraw_cw20_more_than_deposited() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        advance_blocks(&mut app, 20);
+
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(501u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InsufficientFunds { .. }));
+    }
+
+    #[test]
+    fn two_step_admin_handover() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        const NEW_ADMIN: &str = "new_admin";
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::ProposeNewAdmin { admin: USER.to_string() }, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::ProposeNewAdmin { admin: NEW_ADMIN.to_string() }, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+
+        // proposing does not hand over the role yet
+        let config = get_config(&app, &deposit_contract);
+        assert_eq!(config.admin, Addr::unchecked(ADMIN));
+        assert_eq!(config.pending_admin, Some(Addr::unchecked(NEW_ADMIN)));
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::AcceptAdmin {}, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::AcceptAdmin {}, vec![]).unwrap();
+        app.execute(Addr::unchecked(NEW_ADMIN), cosmos_msg).unwrap();
+
+        let config = get_config(&app, &deposit_contract);
+        assert_eq!(config.admin, Addr::unchecked(NEW_ADMIN));
+        assert_eq!(config.pending_admin, None);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::UpdateConfig { lock_duration: None }, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn paused_operations_are_rejected() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(400, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let msg = ExecuteMsg::SetPause { native_deposit: None, native_withdraw: Some(true), cw20_deposit: Some(true), cw20_withdraw: None };
+        let cosmos_msg = deposit_contract.call(msg.clone(), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+
+        let status: PauseState = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::PauseStatus {}).unwrap();
+        assert_eq!(status, PauseState { native_deposit: false, native_withdraw: true, cw20_deposit: true, cw20_withdraw: false });
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(400), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Paused { operation } if operation == "native_withdraw"));
+
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(500u64), msg: to_binary(&hook_msg).unwrap() };
+        let cosmos_msg = cw20_contract.call(msg).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Paused { operation } if operation == "cw20_deposit"));
+
+        // deposits that are not paused still go through
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(100, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+    }
+
+    #[test]
+    fn only_allowed_cw20_can_be_deposited() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let send = |amount: u128| Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(amount), msg: to_binary(&hook_msg).unwrap() };
+
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(send(500)).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::TokenNotAllowed { .. }));
+
+        let msg = ExecuteMsg::AddAllowedToken { contract: cw20_contract.addr().to_string(), min_deposit: Some(Uint128::new(100)), max_deposit: Some(Uint128::new(1000)) };
+        let cosmos_msg = deposit_contract.call(msg.clone(), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+
+        let allowed: AllowedTokensResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllowedTokens { start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(allowed.tokens.len(), 1);
+        assert_eq!(allowed.tokens[0].contract, cw20_contract.addr());
+        assert_eq!(allowed.tokens[0].min_deposit, Some(Uint128::new(100)));
+
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(send(99)).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::DepositTooSmall { min, .. } if min == Uint128::new(100)));
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(send(1001)).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::DepositTooLarge { max, .. } if max == Uint128::new(1000)));
+        app.execute(Addr::unchecked(USER), cw20_contract.call(send(500)).unwrap()).unwrap();
+
+        // removing the token stops new deposits but not withdrawals
+        let msg = ExecuteMsg::RemoveAllowedToken { contract: cw20_contract.addr().to_string() };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(send(500)).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::TokenNotAllowed { .. }));
+
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(500u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+    }
+
+    #[test]
+    fn native_denom_limits() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(100, NATIVE_DENOM)]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::DenomNotAllowed { denom } if denom == NATIVE_DENOM));
+
+        let msg = ExecuteMsg::AddAllowedDenom {
+            denom: NATIVE_DENOM.to_string(),
+            min_deposit: Some(Uint128::new(50)),
+            max_user_balance: Some(Uint128::new(300)),
+            global_cap: Some(Uint128::new(500)),
+        };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+
+        let allowed: AllowedDenomsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllowedDenoms { start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(allowed.denoms.len(), 1);
+        assert_eq!(allowed.denoms[0].global_cap, Some(Uint128::new(500)));
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(49, NATIVE_DENOM)]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::DepositTooSmall { .. }));
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(200, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(101, NATIVE_DENOM)]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::UserBalanceCapExceeded { .. }));
+
+        // a second user fills the contract up to the global cap
+        const OTHER_USER: &str = "other_user";
+        app.send_tokens(Addr::unchecked(USER), Addr::unchecked(OTHER_USER), &[coin(400, NATIVE_DENOM)]).unwrap();
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(OTHER_USER), cosmos_msg).unwrap();
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(50, NATIVE_DENOM)]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::GlobalCapExceeded { .. }));
+
+        // withdrawing frees up room under the global cap
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(100), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(OTHER_USER), cosmos_msg).unwrap();
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(50, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+    }
+
+    #[test]
+    fn migrate_from_v0_1_0() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let legacy_id = app.store_code(contract_legacy_deposit());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let start_height = app.block_info().height;
+
+        let msg = LegacyInstantiateMsg { version: "0.1.0".to_string(), cw20: cw20_contract.addr().to_string() };
+        let legacy_address = app
+            .instantiate_contract(legacy_id, Addr::unchecked(ADMIN), &msg, &[], "deposit-cw20", Some(ADMIN.to_string()))
+            .unwrap();
+        let deposit_contract = DepositContract(legacy_address);
+        let msg = Cw20ExecuteMsg::Transfer { recipient: deposit_contract.addr().to_string(), amount: Uint128::new(500) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        // 0.1.0 had no config, so an admin has to be provided
+        let err = app
+            .migrate_contract(Addr::unchecked(ADMIN), deposit_contract.addr(), &MigrateMsg { admin: None }, deposit_id)
+            .unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::MissingAdmin {}));
+        const NEW_ADMIN: &str = "new_admin";
+        app.migrate_contract(Addr::unchecked(ADMIN), deposit_contract.addr(), &MigrateMsg { admin: Some(NEW_ADMIN.to_string()) }, deposit_id)
+            .unwrap();
+
+        let version = cw2::query_contract_info(&app, deposit_contract.addr()).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+        let config = get_config(&app, &deposit_contract);
+        assert_eq!(config.admin, Addr::unchecked(NEW_ADMIN));
+        assert_eq!(config.lock_duration, Duration::Height(20));
+
+        // the aggregate deposit became a single lot that keeps its stake time
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots.len(), 1);
+        assert_eq!(lots.lots[0].amount, Uint128::new(500));
+        assert_eq!(lots.lots[0].unlocks_at, Expiration::AtHeight(start_height + 20));
+        // the deposit height of older lots is unknown, they count from the migration
+        assert_eq!(lots.lots[0].deposited_at.height, app.block_info().height);
+        // with the default lock, the whole amount counts for rewards
+        assert_eq!(lots.lots[0].lock_tier, None);
+        assert_eq!(lots.lots[0].weight, Uint128::new(500));
+        assert_eq!(lots.lots[0].memo, None);
+        assert_eq!(config.early_withdraw, None);
+        assert_eq!(config.unbonding_period, None);
+        let balance = get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract);
+        assert_eq!(balance, Cw20BalanceResponse { locked: Uint128::new(500), withdrawable: Uint128::zero() });
+
+        // running totals are rebuilt from the migrated deposits
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(500));
+        let total: DenomTotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomTotal { denom: NATIVE_DENOM.to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(400));
+
+        // the indexes were built for the existing deposits
+        let holders: HoldersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomHolders { denom: NATIVE_DENOM.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(holders.holders, vec![Holder { address: Addr::unchecked(USER), amount: Uint128::new(400) }]);
+        let holders: HoldersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Holders { contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(holders.holders, vec![Holder { address: Addr::unchecked(USER), amount: Uint128::new(500) }]);
+
+        // the empty record is dropped and the count becomes the deposit counter
+        let deposits = get_deposits(&app, &deposit_contract);
+        assert_eq!(deposits.deposits.len(), 1);
+        assert_eq!(deposits.deposits[0].1.deposit_count, 1);
+        assert_eq!(deposits.deposits[0].1.withdraw_count, 0);
+        assert_eq!(deposits.deposits[0].1.last_deposit, None);
+        let holders: HoldersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomHolders { denom: OTHER_DENOM.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert!(holders.holders.is_empty());
+
+        // both kinds of deposits end up in the balances
+        let res: BalancesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Balances { address: USER.to_string(), start_after: None, limit: None })
+            .unwrap();
+        let assets: Vec<_> = res.balances.into_iter().map(|balance| balance.asset).collect();
+        assert_eq!(assets, vec![
+            AssetInfo::Cw20 { contract: cw20_contract.addr().to_string() },
+            AssetInfo::Native { denom: NATIVE_DENOM.to_string() },
+        ]);
+
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(500u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        let balance = get_cw20_balance(&app, &cw20_contract, USER.to_string());
+        assert_eq!(Uint128::from(10000u64), balance.balance);
+    }
+
+    #[test]
+    fn migrate_refuses_downgrade() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let legacy_id = app.store_code(contract_legacy_deposit());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = LegacyInstantiateMsg { version: "9.9.9".to_string(), cw20: cw20_contract.addr().to_string() };
+        let legacy_address = app
+            .instantiate_contract(legacy_id, Addr::unchecked(ADMIN), &msg, &[], "deposit-cw20", Some(ADMIN.to_string()))
+            .unwrap();
+        let err = app
+            .migrate_contract(Addr::unchecked(ADMIN), legacy_address, &MigrateMsg { admin: None }, deposit_id)
+            .unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::CannotDowngrade { .. }));
+    }
+
+    #[test]
+    fn totals_and_depositors() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+        allow_denom(&mut app, &deposit_contract, OTHER_DENOM);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        const OTHER_USER: &str = "other_user";
+        app.send_tokens(Addr::unchecked(USER), Addr::unchecked(OTHER_USER), &[coin(300, NATIVE_DENOM)]).unwrap();
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(400, NATIVE_DENOM), coin(100, OTHER_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(OTHER_USER), cosmos_msg).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(50), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(OTHER_USER), cosmos_msg).unwrap();
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string(), amount:Uint128::from(200u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let depositors: DepositorsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllDepositors { start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(depositors.depositors, vec![Addr::unchecked(USER), Addr::unchecked(OTHER_USER)]);
+
+        let totals: AllDenomTotalsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllDenomTotals { start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(totals.totals, vec![
+            DenomTotalResponse { denom: NATIVE_DENOM.to_string(), total: Uint128::new(650) },
+            DenomTotalResponse { denom: OTHER_DENOM.to_string(), total: Uint128::new(100) },
+        ]);
+
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(300));
+    }
+
+    #[test]
+    fn holders_ordered_by_amount() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        for (user, amount) in [("user_a", 100u128), ("user_b", 300), ("user_c", 200)] {
+            app.send_tokens(Addr::unchecked(USER), Addr::unchecked(user), &[coin(amount, NATIVE_DENOM)]).unwrap();
+            let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(amount, NATIVE_DENOM)]).unwrap();
+            app.execute(Addr::unchecked(user), cosmos_msg).unwrap();
+        }
+
+        let holders: HoldersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomHolders { denom: NATIVE_DENOM.to_string(), start_after: None, limit: Some(2) })
+            .unwrap();
+        assert_eq!(holders.holders, vec![
+            Holder { address: Addr::unchecked("user_b"), amount: Uint128::new(300) },
+            Holder { address: Addr::unchecked("user_c"), amount: Uint128::new(200) },
+        ]);
+
+        let holders: HoldersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomHolders {
+                denom: NATIVE_DENOM.to_string(),
+                start_after: holders.holders.last().cloned(),
+                limit: Some(2),
+            })
+            .unwrap();
+        assert_eq!(holders.holders, vec![Holder { address: Addr::unchecked("user_a"), amount: Uint128::new(100) }]);
+
+        // a withdraw moves the holder down the index
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(250), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked("user_b"), cosmos_msg).unwrap();
+        let holders: HoldersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomHolders { denom: NATIVE_DENOM.to_string(), start_after: None, limit: None })
+            .unwrap();
+        let order: Vec<_> = holders.holders.iter().map(|h| h.address.as_str()).collect();
+        assert_eq!(order, vec!["user_c", "user_a", "user_b"]);
+    }
+
+    #[test]
+    fn addresses_are_validated() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        advance_blocks(&mut app, 20);
+
+        // a case variant of the cw20 address does not reach a separate record
+        let msg = ExecuteMsg::WithdrawCw20 {address:cw20_contract.addr().to_string().to_uppercase(), amount:Uint128::from(500u64), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Std(_)));
+
+        let res: StdResult<Cw20DepositResponse> = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: USER.to_uppercase(), start_after: None, limit: None });
+        assert!(res.is_err());
+
+        let deposits = get_cw20_deposits(&app, &deposit_contract);
+        assert_eq!(deposits.deposits[0].0, cw20_contract.addr());
+        assert_eq!(deposits.deposits[0].1.owner, Addr::unchecked(USER));
+    }
+
+    #[test]
+    fn withdraw_to_recipient() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        advance_blocks(&mut app, 20);
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(100), recipient: Some("friend".to_string()) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_balance(&app, "friend".to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(100));
+        assert_eq!(get_balance(&app, USER.to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(700));
+
+        let msg = ExecuteMsg::WithdrawCw20 { address: cw20_contract.addr().to_string(), amount: Uint128::new(200), recipient: Some("friend".to_string()) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "friend".to_string()).balance, Uint128::new(200));
+
+        // the recipient is validated
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(100), recipient: Some("FRIEND".to_string()) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Std(_)));
+    }
+
+    #[test]
+    fn withdraw_all_and_everything() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+        allow_denom(&mut app, &deposit_contract, OTHER_DENOM);
+
+        let funds = vec![coin(300, NATIVE_DENOM), coin(200, OTHER_DENOM)];
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, funds).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawAll { denom: NATIVE_DENOM.to_string() }, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(1000));
+        let deposits = get_deposits(&app, &deposit_contract);
+        assert_eq!(deposits.deposits.len(), 1);
+        assert_eq!(deposits.deposits[0].0, OTHER_DENOM);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawAll { denom: NATIVE_DENOM.to_string() }, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoDepositForDenom { .. }));
+
+        // only matured lots are drained
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() }, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+
+        advance_blocks(&mut app, 20);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 100);
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() }, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9900));
+        let balance = get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract);
+        assert_eq!(balance.locked, Uint128::new(100));
+
+        advance_blocks(&mut app, 20);
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawEverything { }, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), OTHER_DENOM.to_string()).amount, Uint128::new(1000));
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(10000));
+        assert!(get_deposits(&app, &deposit_contract).deposits.is_empty());
+        assert!(get_cw20_deposits(&app, &deposit_contract).deposits.is_empty());
+
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::zero());
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawEverything { }, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NothingToWithdraw {}));
+    }
+
+    #[test]
+    fn deposit_counters_track_activity() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        for _ in 0..2 {
+            let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(100, NATIVE_DENOM)]).unwrap();
+            app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+            advance_blocks(&mut app, 1);
+        }
+        let deposit_height = app.block_info().height - 1;
+
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(50), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let deposits = get_deposits(&app, &deposit_contract);
+        let deposit = &deposits.deposits[0].1;
+        assert_eq!(deposit.deposit_count, 2);
+        assert_eq!(deposit.withdraw_count, 1);
+        assert_eq!(deposit.last_deposit.as_ref().unwrap().height, deposit_height);
+        let last_withdraw = deposit.last_withdraw.as_ref().unwrap();
+        assert_eq!(last_withdraw.height, app.block_info().height);
+        assert_eq!(last_withdraw.time, app.block_info().time);
+
+        // withdrawing the rest removes the record, a new deposit starts over
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(150), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert!(get_deposits(&app, &deposit_contract).deposits.is_empty());
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(100, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        let deposits = get_deposits(&app, &deposit_contract);
+        assert_eq!(deposits.deposits[0].1.deposit_count, 1);
+        assert_eq!(deposits.deposits[0].1.withdraw_count, 0);
+        assert_eq!(deposits.deposits[0].1.last_withdraw, None);
+    }
+
+    #[test]
+    fn transfer_native_deposit() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let msg = ExecuteMsg::TransferDeposit { recipient: "friend".to_string(), denom: NATIVE_DENOM.to_string(), amount: Uint128::new(100) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        // only the ledger moved
+        assert_eq!(get_balance(&app, deposit_contract.addr().to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(300));
+        assert_eq!(get_balance(&app, "friend".to_string(), NATIVE_DENOM.to_string()).amount, Uint128::zero());
+        let total: DenomTotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomTotal { denom: NATIVE_DENOM.to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(300));
+        assert_eq!(get_deposits(&app, &deposit_contract).deposits[0].1.coins.amount, Uint128::new(200));
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::WithdrawAll { denom: NATIVE_DENOM.to_string() }, vec![]).unwrap();
+        app.execute(Addr::unchecked("friend"), cosmos_msg).unwrap();
+        assert_eq!(get_balance(&app, "friend".to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(100));
+
+        let msg = ExecuteMsg::TransferDeposit { recipient: "friend".to_string(), denom: NATIVE_DENOM.to_string(), amount: Uint128::new(201) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InsufficientFunds { .. }));
+
+        let msg = ExecuteMsg::TransferDeposit { recipient: USER.to_string(), denom: NATIVE_DENOM.to_string(), amount: Uint128::new(100) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::CannotTransferToSelf {}));
+    }
+
+    #[test]
+    fn transfer_cw20_deposit_keeps_locks() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 300);
+        advance_blocks(&mut app, 10);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 200);
+        let start_height = app.block_info().height;
+
+        // takes the whole first lot and part of the second one
+        let msg = ExecuteMsg::TransferCw20Deposit { recipient: "friend".to_string(), contract: cw20_contract.addr().to_string(), amount: Uint128::new(400) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: "friend".to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        let moved: Vec<_> = lots.lots.iter().map(|lot| (lot.amount.u128(), lot.unlocks_at)).collect();
+        assert_eq!(moved, vec![
+            (300, Expiration::AtHeight(start_height + 10)),
+            (100, Expiration::AtHeight(start_height + 20)),
+        ]);
+        assert_eq!(get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract).locked, Uint128::new(100));
+
+        // another part of the same lot joins the lot already moved
+        let msg = ExecuteMsg::TransferCw20Deposit { recipient: "friend".to_string(), contract: cw20_contract.addr().to_string(), amount: Uint128::new(50) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: "friend".to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots.len(), 2);
+        assert_eq!(lots.lots[1].amount, Uint128::new(150));
+
+        // the recipient waits for the same unlock as the sender would have
+        let msg = ExecuteMsg::WithdrawCw20 { address: cw20_contract.addr().to_string(), amount: Uint128::new(400), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg.clone(), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked("friend"), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+
+        advance_blocks(&mut app, 20);
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked("friend"), cosmos_msg).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "friend".to_string()).balance, Uint128::new(400));
+
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(100));
+    }
+
+    #[test]
+    fn withdraw_skips_locked_older_lots() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 100);
+        let msg = ExecuteMsg::SetCw20LockDuration { contract: cw20_contract.addr().to_string(), lock_duration: Some(Duration::Height(5)) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 50);
+        advance_blocks(&mut app, 5);
+
+        // the newer lot matured first, the older one stays locked
+        let msg = ExecuteMsg::WithdrawCw20 { address: cw20_contract.addr().to_string(), amount: Uint128::new(50), recipient: None };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots.len(), 1);
+        assert_eq!(lots.lots[0].amount, Uint128::new(100));
+        assert_eq!(get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract).locked, Uint128::new(100));
+    }
+
+    #[test]
+    fn allowances_let_spenders_withdraw() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+
+        let native = AssetInfo::Native { denom: NATIVE_DENOM.to_string() };
+        let cw20 = AssetInfo::Cw20 { contract: cw20_contract.addr().to_string() };
+        for (asset, amount) in [(native.clone(), 100u128), (cw20.clone(), 200)] {
+            let msg = ExecuteMsg::IncreaseDepositAllowance { spender: "bot".to_string(), asset, amount: Uint128::new(amount), expires: None };
+            app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        }
+
+        let withdraw_from = |asset: &AssetInfo, amount: u128| ExecuteMsg::WithdrawFrom {
+            owner: USER.to_string(),
+            asset: asset.clone(),
+            amount: Uint128::new(amount),
+            recipient: Some("custody".to_string()),
+        };
+        app.execute(Addr::unchecked("bot"), deposit_contract.call(withdraw_from(&native, 60), vec![]).unwrap()).unwrap();
+        assert_eq!(get_balance(&app, "custody".to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(60));
+        assert_eq!(get_deposits(&app, &deposit_contract).deposits[0].1.coins.amount, Uint128::new(240));
+
+        let allowance: AllowanceResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Allowance { owner: USER.to_string(), spender: "bot".to_string(), asset: native.clone() })
+            .unwrap();
+        assert_eq!(allowance, AllowanceResponse { allowance: Uint128::new(40), expires: Expiration::Never {} });
+
+        let err = app.execute(Addr::unchecked("bot"), deposit_contract.call(withdraw_from(&native, 41), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InsufficientAllowance { .. }));
+        let err = app.execute(Addr::unchecked("other_bot"), deposit_contract.call(withdraw_from(&native, 10), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoAllowance {}));
+
+        // cw20 withdraws through an allowance still wait for the lock
+        let err = app.execute(Addr::unchecked("bot"), deposit_contract.call(withdraw_from(&cw20, 200), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+        advance_blocks(&mut app, 20);
+        app.execute(Addr::unchecked("bot"), deposit_contract.call(withdraw_from(&cw20, 200), vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "custody".to_string()).balance, Uint128::new(200));
+
+        // a used up allowance is removed
+        let allowances: AllAllowancesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllAllowances { owner: USER.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(allowances.allowances.len(), 1);
+        assert_eq!(allowances.allowances[0].asset, native);
+
+        let msg = ExecuteMsg::DecreaseDepositAllowance { spender: "bot".to_string(), asset: native.clone(), amount: Uint128::new(100), expires: None };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let err = app.execute(Addr::unchecked("bot"), deposit_contract.call(withdraw_from(&native, 10), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoAllowance {}));
+    }
+
+    #[test]
+    fn allowance_expiration_and_listing() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+
+        let native = AssetInfo::Native { denom: NATIVE_DENOM.to_string() };
+        let height = app.block_info().height;
+        let msg = ExecuteMsg::IncreaseDepositAllowance { spender: USER.to_string(), asset: native.clone(), amount: Uint128::new(100), expires: None };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::CannotSetOwnAccount {}));
+        let msg = ExecuteMsg::IncreaseDepositAllowance { spender: "bot".to_string(), asset: native.clone(), amount: Uint128::new(100), expires: Some(Expiration::AtHeight(height)) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidExpiration {}));
+
+        for spender in ["bot_c", "bot_a", "bot_b"] {
+            let msg = ExecuteMsg::IncreaseDepositAllowance { spender: spender.to_string(), asset: native.clone(), amount: Uint128::new(100), expires: Some(Expiration::AtHeight(height + 5)) };
+            app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        }
+        let allowances: AllAllowancesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllAllowances { owner: USER.to_string(), start_after: None, limit: Some(2) })
+            .unwrap();
+        let spenders: Vec<_> = allowances.allowances.iter().map(|a| a.spender.as_str()).collect();
+        assert_eq!(spenders, vec!["bot_a", "bot_b"]);
+        let start_after = AllowanceKey { spender: "bot_b".to_string(), asset: native.clone() };
+        let allowances: AllAllowancesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllAllowances { owner: USER.to_string(), start_after: Some(start_after), limit: None })
+            .unwrap();
+        assert_eq!(allowances.allowances.len(), 1);
+        assert_eq!(allowances.allowances[0].spender, Addr::unchecked("bot_c"));
+
+        advance_blocks(&mut app, 5);
+        let msg = ExecuteMsg::WithdrawFrom { owner: USER.to_string(), asset: native.clone(), amount: Uint128::new(10), recipient: None };
+        let err = app.execute(Addr::unchecked("bot_a"), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Expired {}));
+
+        // increasing an expired allowance starts from zero
+        let msg = ExecuteMsg::IncreaseDepositAllowance { spender: "bot_a".to_string(), asset: native.clone(), amount: Uint128::new(30), expires: None };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let allowance: AllowanceResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Allowance { owner: USER.to_string(), spender: "bot_a".to_string(), asset: native })
+            .unwrap();
+        assert_eq!(allowance, AllowanceResponse { allowance: Uint128::new(30), expires: Expiration::Never {} });
+    }
+
+    fn pending_rewards(app: &App, deposit_contract: &DepositContract, address: &str) -> Uint128 {
+        let res: PendingRewardsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::PendingRewards { address: address.to_string() })
+            .unwrap();
+        res.rewards.iter().map(|reward| reward.amount).sum()
+    }
+
+    #[test]
+    fn rewards_follow_deposits_and_withdraws() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        // 5 other per second, funded by the admin
+        let reward = AssetInfo::Native { denom: OTHER_DENOM.to_string() };
+        let msg = ExecuteMsg::SetRewardPool { contract: cw20_contract.addr().to_string(), reward, emission_per_second: Uint128::new(5) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.send_tokens(Addr::unchecked(USER), Addr::unchecked(ADMIN), &[coin(1000, OTHER_DENOM)]).unwrap();
+        let msg = ExecuteMsg::FundRewards { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![coin(1000, OTHER_DENOM)]).unwrap()).unwrap();
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "staker".to_string(), amount: Uint128::new(250) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        // alone for 50 seconds
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 250);
+        advance_blocks(&mut app, 10);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(250));
+
+        // a second depositor joins mid-epoch and the emission is split by deposit
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(250), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked("staker"), cw20_contract.call(msg).unwrap()).unwrap();
+        advance_blocks(&mut app, 10);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(375));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(125));
+
+        // after a withdraw the remaining depositor gets everything, earned rewards are kept
+        let msg = ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        advance_blocks(&mut app, 10);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(375));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(375));
+
+        // native rewards stay put while native withdraws are paused
+        let pause = |paused: bool| ExecuteMsg::SetPause { native_deposit: None, native_withdraw: Some(paused), cw20_deposit: None, cw20_withdraw: None };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(pause(true), vec![]).unwrap()).unwrap();
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Paused { operation } if operation == "native_withdraw"));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(pause(false), vec![]).unwrap()).unwrap();
+
+        app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), OTHER_DENOM.to_string()).amount, Uint128::new(375));
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::zero());
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoRewardsToClaim {}));
+
+        app.execute(Addr::unchecked("staker"), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_balance(&app, "staker".to_string(), OTHER_DENOM.to_string()).amount, Uint128::new(375));
+    }
+
+    #[test]
+    fn rewards_index_fits_a_large_emission_over_a_tiny_stake() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        // a million tokens with 18 decimals, emitted over 10 seconds to a stake of one base unit
+        let funds = coin(1_000_000_000_000_000_000_000_000, "big");
+        app.sudo(SudoMsg::Bank(BankSudo::Mint { to_address: USER.to_string(), amount: vec![funds.clone()] })).unwrap();
+        app.send_tokens(Addr::unchecked(USER), Addr::unchecked(ADMIN), std::slice::from_ref(&funds)).unwrap();
+        let reward = AssetInfo::Native { denom: "big".to_string() };
+        let emission_per_second = funds.amount / Uint128::new(10);
+        let msg = ExecuteMsg::SetRewardPool { contract: cw20_contract.addr().to_string(), reward, emission_per_second };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let msg = ExecuteMsg::FundRewards { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![funds.clone()]).unwrap()).unwrap();
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 1);
+        advance_blocks(&mut app, 20);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), funds.amount);
+
+        // deposits, withdraws and claims keep working past the index of a `Decimal`
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 10);
+        let msg = ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), "big".to_string()).amount, funds.amount);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::zero());
+    }
+
+    #[test]
+    fn rewards_stop_when_the_pool_runs_dry() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        // the admin needs a valid address to hold the cw20 it funds the pool with
+        const TREASURY: &str = "treasury";
+        let msg = ExecuteMsg::ProposeNewAdmin { admin: TREASURY.to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.execute(Addr::unchecked(TREASURY), deposit_contract.call(ExecuteMsg::AcceptAdmin {}, vec![]).unwrap()).unwrap();
+
+        // the deposited cw20 also pays the rewards
+        let reward = AssetInfo::Cw20 { contract: cw20_contract.addr().to_string() };
+        let msg = ExecuteMsg::SetRewardPool { contract: cw20_contract.addr().to_string(), reward: reward.clone(), emission_per_second: Uint128::new(5) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg.clone(), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        app.execute(Addr::unchecked(TREASURY), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+
+        let msg = ExecuteMsg::FundRewards { contract: cw20_contract.addr().to_string() };
+        let err = app.execute(Addr::unchecked(TREASURY), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoFunds {}));
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: TREASURY.to_string(), amount: Uint128::new(100) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let hook_msg = Cw20HookMsg::FundRewards { contract: cw20_contract.addr().to_string() };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(100), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked(TREASURY), cw20_contract.call(msg).unwrap()).unwrap();
+
+        // nothing is emitted while nobody has deposited
+        advance_blocks(&mut app, 10);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 250);
+        advance_blocks(&mut app, 40);
+        let pending: PendingRewardsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::PendingRewards { address: USER.to_string() })
+            .unwrap();
+        assert_eq!(pending.rewards.len(), 1);
+        assert_eq!(pending.rewards[0].reward, reward);
+        assert_eq!(pending.rewards[0].amount, Uint128::new(100));
+
+        app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9750));
+        // the deposit itself is untouched
+        assert_eq!(get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract).withdrawable, Uint128::new(250));
+    }
+
+    fn set_early_withdraw_policy(app: &mut App, deposit_contract: &DepositContract, max_penalty: &str, destination: PenaltyDestination) {
+        let policy = EarlyWithdrawPolicy { max_penalty: max_penalty.parse::<Decimal>().unwrap(), destination };
+        let msg = ExecuteMsg::SetEarlyWithdrawPolicy { policy: Some(policy) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn early_withdraw_penalty_decays() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 400);
+
+        let early_withdraw = |amount: u128| ExecuteMsg::EarlyWithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::new(amount) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(early_withdraw(400), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::EarlyWithdrawDisabled {}));
+
+        let policy = EarlyWithdrawPolicy { max_penalty: Decimal::percent(101), destination: PenaltyDestination::Stakers {} };
+        let msg = ExecuteMsg::SetEarlyWithdrawPolicy { policy: Some(policy) };
+        let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidPenalty {}));
+        set_early_withdraw_policy(&mut app, &deposit_contract, "0.5", PenaltyDestination::Treasury { address: Addr::unchecked("treasury") });
+        assert!(get_config(&app, &deposit_contract).early_withdraw.is_some());
+
+        // half way to unlock, half of the 50% penalty is due
+        advance_blocks(&mut app, 10);
+        app.execute(Addr::unchecked(USER), deposit_contract.call(early_withdraw(400), vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9900));
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "treasury".to_string()).balance, Uint128::new(100));
+        assert!(get_cw20_deposits(&app, &deposit_contract).deposits.is_empty());
+
+        // matured lots are taken first and carry no penalty
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 100);
+        advance_blocks(&mut app, 20);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 200);
+        app.execute(Addr::unchecked(USER), deposit_contract.call(early_withdraw(150), vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "treasury".to_string()).balance, Uint128::new(125));
+        assert_eq!(get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract).locked, Uint128::new(150));
+
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(150));
+    }
+
+    #[test]
+    fn early_withdraw_penalty_shared_with_stakers() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        set_early_withdraw_policy(&mut app, &deposit_contract, "0.5", PenaltyDestination::Stakers {});
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "staker".to_string(), amount: Uint128::new(100) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(100), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked("staker"), cw20_contract.call(msg).unwrap()).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 400);
+
+        // leaving right away pays the full penalty to the remaining depositor
+        let msg = ExecuteMsg::EarlyWithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::new(400) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9700));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(200));
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::zero());
+        // the shares stay in the contract next to the deposits until they are claimed
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(100));
+        assert_eq!(total.penalties, Uint128::new(200));
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, deposit_contract.addr().to_string()).balance, total.total + total.penalties);
+
+        // penalty shares are paid in the cw20, so they wait while cw20 withdraws are paused
+        let pause = |paused: bool| ExecuteMsg::SetPause { native_deposit: None, native_withdraw: None, cw20_deposit: None, cw20_withdraw: Some(paused) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(pause(true), vec![]).unwrap()).unwrap();
+        let err = app.execute(Addr::unchecked("staker"), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Paused { operation } if operation == "cw20_withdraw"));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(pause(false), vec![]).unwrap()).unwrap();
+
+        app.execute(Addr::unchecked("staker"), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "staker".to_string()).balance, Uint128::new(200));
+        // the deposit of the staker stays in place
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(100));
+        assert_eq!(total.penalties, Uint128::zero());
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, deposit_contract.addr().to_string()).balance, Uint128::new(100));
+    }
+
+    #[test]
+    fn early_withdraw_penalty_skips_the_withdrawer() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        set_early_withdraw_policy(&mut app, &deposit_contract, "0.5", PenaltyDestination::Stakers {});
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "staker".to_string(), amount: Uint128::new(100) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(100), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked("staker"), cw20_contract.call(msg).unwrap()).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 400);
+
+        // the part of the deposit kept by the withdrawer earns nothing from their own penalty
+        let early_withdraw = |amount: u128| ExecuteMsg::EarlyWithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::new(amount) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(early_withdraw(200), vec![]).unwrap()).unwrap();
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(100));
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::zero());
+
+        // later penalties of the staker are still shared with the withdrawer
+        let msg = ExecuteMsg::EarlyWithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::new(40) };
+        app.execute(Addr::unchecked("staker"), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(20));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(100));
+
+        // a sole depositor keeping part of the stake pays the penalty to the admin
+        let msg = ExecuteMsg::ProposeNewAdmin { admin: "admin".to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.execute(Addr::unchecked("admin"), deposit_contract.call(ExecuteMsg::AcceptAdmin {}, vec![]).unwrap()).unwrap();
+        let other_contract = cw_20_instantiate(&mut app, cw20_id);
+        let msg = ExecuteMsg::AddAllowedToken { contract: other_contract.addr().to_string(), min_deposit: None, max_deposit: None };
+        app.execute(Addr::unchecked("admin"), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        send_cw20(&mut app, &other_contract, &deposit_contract, 400);
+        let msg = ExecuteMsg::EarlyWithdrawCw20 { contract: other_contract.addr().to_string(), amount: Uint128::new(399) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &other_contract, "admin".to_string()).balance, Uint128::new(199));
+        assert_eq!(get_cw20_balance(&app, &other_contract, USER.to_string()).balance, Uint128::new(9800));
+    }
+
+    #[test]
+    fn large_penalty_over_a_tiny_stake() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let supply = Uint128::new(1_000_000_000_000_000_000_000_000);
+        let msg = Cw20InstantiateMsg {
+            decimals: 18,
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            initial_balances: vec![Cw20Coin { address: USER.to_string(), amount: supply }],
+            marketing: None,
+            mint: None,
+        };
+        let address = app.instantiate_contract(cw20_id, Addr::unchecked(ADMIN), &msg, &[], "cw20-example", None).unwrap();
+        let cw20_contract = Cw20Contract(address);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        set_early_withdraw_policy(&mut app, &deposit_contract, "0.5", PenaltyDestination::Stakers {});
+
+        // a stake of one base unit takes the whole penalty of a large early withdraw
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "staker".to_string(), amount: Uint128::new(1) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(1), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked("staker"), cw20_contract.call(msg).unwrap()).unwrap();
+        let deposited = supply - Uint128::new(1);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, deposited.u128());
+        let msg = ExecuteMsg::EarlyWithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: deposited };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let penalty = deposited / Uint128::new(2);
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), penalty);
+
+        // later settlements of the cw20 still go through
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 10);
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked("staker"), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.execute(Addr::unchecked("staker"), deposit_contract.call(ExecuteMsg::ClaimRewards {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, "staker".to_string()).balance, penalty + Uint128::new(1));
+    }
+
+    #[test]
+    fn unbonding_queue() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+
+        let unbond = |amount: u128| ExecuteMsg::UnbondCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::new(amount) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(100), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::UnbondingDisabled {}));
+
+        let msg = ExecuteMsg::SetUnbondingPeriod { period: Some(Duration::Height(10)) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg.clone(), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(get_config(&app, &deposit_contract).unbonding_period, Some(Duration::Height(10)));
+
+        // withdraws have to go through unbonding
+        let msg = ExecuteMsg::WithdrawCw20 { address: cw20_contract.addr().to_string(), amount: Uint128::new(100), recipient: None };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::UnbondingRequired {}));
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::WithdrawEverything {}, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NothingToWithdraw {}));
+
+        // the unbonding period replaces the default lock, so the locked lot unbonds right away
+        let release_height = app.block_info().height + 10;
+        app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(300), vec![]).unwrap()).unwrap();
+        advance_blocks(&mut app, 5);
+        app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(100), vec![]).unwrap()).unwrap();
+
+        let claims: ClaimsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Claims { address: USER.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(claims.claims.len(), 2);
+        assert_eq!(claims.claims[0].amount, Uint128::new(300));
+        assert_eq!(claims.claims[0].release_at, Expiration::AtHeight(release_height));
+
+        // claims page by id
+        let msg = QueryMsg::Claims { address: USER.to_string(), start_after: None, limit: Some(1) };
+        let page: ClaimsResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &msg).unwrap();
+        assert_eq!(page.claims, vec![claims.claims[0].clone()]);
+        let msg = QueryMsg::Claims { address: USER.to_string(), start_after: Some(page.claims[0].id), limit: Some(1) };
+        let page: ClaimsResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &msg).unwrap();
+        assert_eq!(page.claims, vec![claims.claims[1].clone()]);
+
+        // unbonding tokens left the deposit, and are counted apart until they are paid
+        assert_eq!(get_cw20_deposits(&app, &deposit_contract).deposits[0].1.amount, Uint128::new(100));
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.total, Uint128::new(100));
+        assert_eq!(total.unbonding, Uint128::new(400));
+
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimCw20 {}, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NothingToClaim {}));
+
+        // only the released claim is paid
+        advance_blocks(&mut app, 5);
+        app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimCw20 {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9800));
+        let claims: ClaimsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Claims { address: USER.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(claims.claims.len(), 1);
+        assert_eq!(claims.claims[0].amount, Uint128::new(100));
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.unbonding, Uint128::new(100));
+
+        advance_blocks(&mut app, 5);
+        app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::ClaimCw20 {}, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9900));
+        let total: Cw20TotalResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Total { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(total.unbonding, Uint128::zero());
+    }
+
+    #[test]
+    fn unbonding_keeps_tier_locks() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        let msg = ExecuteMsg::SetLockTier { name: "boost".to_string(), duration: Duration::Height(30), reward_multiplier: Decimal::percent(300) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let msg = ExecuteMsg::SetUnbondingPeriod { period: Some(Duration::Height(10)) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 100);
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: Some("boost".to_string()) };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(200), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        // only the lot with the default lock can be unbonded while the tier lot is locked
+        let unbond = |amount: u128| ExecuteMsg::UnbondCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::new(amount) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(150), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+        app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(100), vec![]).unwrap()).unwrap();
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(1), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+        assert_eq!(get_cw20_balance_on_deposit(&app, &deposit_contract, &cw20_contract).locked, Uint128::new(200));
+
+        advance_blocks(&mut app, 30);
+        app.execute(Addr::unchecked(USER), deposit_contract.call(unbond(200), vec![]).unwrap()).unwrap();
+        assert!(get_cw20_deposits(&app, &deposit_contract).deposits.is_empty());
+    }
+
+    #[test]
+    fn lock_tiers_weigh_rewards() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        // the default lock can be in seconds
+        let msg = ExecuteMsg::UpdateConfig { lock_duration: Some(Duration::Time(3600)) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+
+        const MONTH: u64 = 30 * 24 * 3600;
+        let set_tier = |name: &str, reward_multiplier: &str| ExecuteMsg::SetLockTier {
+            name: name.to_string(),
+            duration: Duration::Time(MONTH),
+            reward_multiplier: reward_multiplier.parse().unwrap(),
+        };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(set_tier("30d", "2"), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(set_tier("30d", "0"), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidRewardMultiplier { .. }));
+        let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(set_tier("", "2"), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidLockTierName {}));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(set_tier("30d", "2"), vec![]).unwrap()).unwrap();
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(set_tier("7d", "1.2"), vec![]).unwrap()).unwrap();
+        let msg = ExecuteMsg::RemoveLockTier { name: "7d".to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg.clone(), vec![]).unwrap()).unwrap();
+        let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::UnknownLockTier { .. }));
+        let tiers: LockTiersResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::LockTiers { start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(tiers.tiers.len(), 1);
+        assert_eq!(tiers.tiers[0].name, "30d");
+        assert_eq!(tiers.tiers[0].reward_multiplier, Decimal::percent(200));
+
+        // 30 other per second
+        let reward = AssetInfo::Native { denom: OTHER_DENOM.to_string() };
+        let msg = ExecuteMsg::SetRewardPool { contract: cw20_contract.addr().to_string(), reward, emission_per_second: Uint128::new(30) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.send_tokens(Addr::unchecked(USER), Addr::unchecked(ADMIN), &[coin(1000, OTHER_DENOM)]).unwrap();
+        let msg = ExecuteMsg::FundRewards { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![coin(1000, OTHER_DENOM)]).unwrap()).unwrap();
+
+        let deposit = |lock_tier: Option<&str>| Cw20ExecuteMsg::Send {
+            contract: deposit_contract.addr().to_string(),
+            amount: Uint128::new(100),
+            msg: to_binary(&Cw20HookMsg::Deposit { lock_tier: lock_tier.map(str::to_string) }).unwrap(),
+        };
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(deposit(Some("90d"))).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::UnknownLockTier { .. }));
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "staker".to_string(), amount: Uint128::new(100) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let now = app.block_info().time;
+        app.execute(Addr::unchecked(USER), cw20_contract.call(deposit(Some("30d"))).unwrap()).unwrap();
+        app.execute(Addr::unchecked("staker"), cw20_contract.call(deposit(None)).unwrap()).unwrap();
+
+        let lots = |app: &App, address: &str| -> Cw20LotsResponse {
+            app.wrap()
+                .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: address.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+                .unwrap()
+        };
+        let tiered = lots(&app, USER).lots;
+        assert_eq!(tiered[0].unlocks_at, Expiration::AtTime(now.plus_seconds(MONTH)));
+        assert_eq!(tiered[0].lock_tier, Some("30d".to_string()));
+        assert_eq!(tiered[0].weight, Uint128::new(200));
+        let default = lots(&app, "staker").lots;
+        assert_eq!(default[0].unlocks_at, Expiration::AtTime(now.plus_seconds(3600)));
+        assert_eq!(default[0].weight, Uint128::new(100));
+
+        // 300 emitted over 10 seconds, split two to one
+        advance_blocks(&mut app, 2);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(200));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(100));
+
+        // transferred lots keep their weight
+        let msg = ExecuteMsg::TransferCw20Deposit { recipient: "staker".to_string(), contract: cw20_contract.addr().to_string(), amount: Uint128::new(50) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(lots(&app, USER).lots[0].weight, Uint128::new(100));
+        assert_eq!(lots(&app, "staker").lots[1].weight, Uint128::new(100));
+        advance_blocks(&mut app, 2);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(300));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(300));
+    }
+
+    #[test]
+    fn lock_tier_multiplier_is_bounded() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let msg = Cw20InstantiateMsg {
+            decimals: 18,
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            initial_balances: vec![Cw20Coin { address: USER.to_string(), amount: Uint128::MAX }],
+            marketing: None,
+            mint: None,
+        };
+        let address = app.instantiate_contract(cw20_id, Addr::unchecked(ADMIN), &msg, &[], "cw20-example", None).unwrap();
+        let cw20_contract = Cw20Contract(address);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+
+        let set_tier = |reward_multiplier: &str| ExecuteMsg::SetLockTier {
+            name: "max".to_string(),
+            duration: Duration::Height(20),
+            reward_multiplier: reward_multiplier.parse().unwrap(),
+        };
+        let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(set_tier("100.000000000000000001"), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidRewardMultiplier { max } if max == Decimal::percent(10000)));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(set_tier("100"), vec![]).unwrap()).unwrap();
+
+        // a weight past the range of the totals is an error rather than an abort
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: Some("max".to_string()) };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::MAX / Uint128::new(10), msg: to_binary(&hook_msg).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Overflow(_)));
+    }
+
+    #[test]
+    fn matured_tier_lots_lose_their_boost() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        let msg = ExecuteMsg::SetLockTier { name: "boost".to_string(), duration: Duration::Height(4), reward_multiplier: Decimal::percent(300) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+
+        // 10 other per second
+        let reward = AssetInfo::Native { denom: OTHER_DENOM.to_string() };
+        let msg = ExecuteMsg::SetRewardPool { contract: cw20_contract.addr().to_string(), reward, emission_per_second: Uint128::new(10) };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        app.send_tokens(Addr::unchecked(USER), Addr::unchecked(ADMIN), &[coin(1000, OTHER_DENOM)]).unwrap();
+        let msg = ExecuteMsg::FundRewards { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![coin(1000, OTHER_DENOM)]).unwrap()).unwrap();
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "staker".to_string(), amount: Uint128::new(100) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: Some("boost".to_string()) };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(100), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let hook_msg = Cw20HookMsg::Deposit { lock_tier: None };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::new(100), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked("staker"), cw20_contract.call(msg).unwrap()).unwrap();
+
+        // the boost pays until the lot matures, up to the next settlement of its owner
+        advance_blocks(&mut app, 4);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(150));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(50));
+        let msg = ExecuteMsg::ReweightCw20 { address: USER.to_string(), contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked("staker"), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots[0].weight, Uint128::new(100));
+
+        // from then on both lots weigh their amount
+        advance_blocks(&mut app, 2);
+        assert_eq!(pending_rewards(&app, &deposit_contract, USER), Uint128::new(200));
+        assert_eq!(pending_rewards(&app, &deposit_contract, "staker"), Uint128::new(100));
+        let reward_pool: RewardPool = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::RewardPool { contract: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(reward_pool.undistributed, Uint128::new(700));
+    }
+
+    #[test]
+    fn deposit_for_beneficiary_with_memo() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+        const BENEFICIARY: &str = "beneficiary";
+
+        let msg = ExecuteMsg::DepositFor { beneficiary: "INVALID".to_string(), memo: None };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![coin(300, NATIVE_DENOM)]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidOwner {}));
+
+        // native coins are credited to the beneficiary only
+        let msg = ExecuteMsg::DepositFor { beneficiary: BENEFICIARY.to_string(), memo: Some("order 42".to_string()) };
+        let res = app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![coin(300, NATIVE_DENOM)]).unwrap()).unwrap();
+        assert!(res.has_event(&Event::new("wasm")
+            .add_attribute("owner", BENEFICIARY)
+            .add_attribute("depositor", USER)
+            .add_attribute("memo", "order 42")));
+        assert!(get_deposits(&app, &deposit_contract).deposits.is_empty());
+        let deposits: DepositResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Deposits { address: BENEFICIARY.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(deposits.deposits[0].1.coins, coin(300, NATIVE_DENOM));
+        let msg = ExecuteMsg::WithdrawAll { denom: NATIVE_DENOM.to_string() };
+        app.execute(Addr::unchecked(BENEFICIARY), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(get_balance(&app, BENEFICIARY.to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(300));
+
+        let deposit_for = |memo: String| Cw20ExecuteMsg::Send {
+            contract: deposit_contract.addr().to_string(),
+            amount: Uint128::new(500),
+            msg: to_binary(&Cw20HookMsg::DepositFor { beneficiary: BENEFICIARY.to_string(), memo: Some(memo), lock_tier: None }).unwrap(),
+        };
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(deposit_for("x".repeat(257))).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::MemoTooLong { max: 256 }));
+
+        // the memo is stored on the lot of the beneficiary
+        let res = app.execute(Addr::unchecked(USER), cw20_contract.call(deposit_for("invoice 7".to_string())).unwrap()).unwrap();
+        assert!(res.has_event(&Event::new("wasm")
+            .add_attribute("owner", BENEFICIARY)
+            .add_attribute("depositor", USER)
+            .add_attribute("memo", "invoice 7")));
+        assert!(get_cw20_deposits(&app, &deposit_contract).deposits.is_empty());
+        let lots: Cw20LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: BENEFICIARY.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots[0].amount, Uint128::new(500));
+        assert_eq!(lots.lots[0].memo, Some("invoice 7".to_string()));
+
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(BENEFICIARY), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, BENEFICIARY.to_string()).balance, Uint128::new(500));
+    }
+
+    #[test]
+    fn deposits_for_others_cannot_flood_the_lots() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        let msg = Cw20ExecuteMsg::Transfer { recipient: "attacker".to_string(), amount: Uint128::new(100) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        let deposit_for = Cw20ExecuteMsg::Send {
+            contract: deposit_contract.addr().to_string(),
+            amount: Uint128::new(1),
+            msg: to_binary(&Cw20HookMsg::DepositFor { beneficiary: USER.to_string(), memo: None, lock_tier: None }).unwrap(),
+        };
+        let lots = |app: &App| -> Vec<Cw20Lot> {
+            let mut lots: Vec<Cw20Lot> = vec![];
+            loop {
+                let start_after = lots.last().map(|lot| lot.id);
+                let res: Cw20LotsResponse = app.wrap()
+                    .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Lots { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after, limit: Some(30) })
+                    .unwrap();
+                if res.lots.is_empty() {
+                    return lots;
+                }
+                lots.extend(res.lots);
+            }
+        };
+
+        // deposits with the same lock in the same block share a lot
+        app.execute(Addr::unchecked("attacker"), cw20_contract.call(deposit_for.clone()).unwrap()).unwrap();
+        app.execute(Addr::unchecked("attacker"), cw20_contract.call(deposit_for.clone()).unwrap()).unwrap();
+        assert_eq!(lots(&app).len(), 1);
+        assert_eq!(lots(&app)[0].amount, Uint128::new(2));
+
+        // others can only bring the owner to 40 lots
+        for _ in 1..40 {
+            advance_blocks(&mut app, 1);
+            app.execute(Addr::unchecked("attacker"), cw20_contract.call(deposit_for.clone()).unwrap()).unwrap();
+        }
+        advance_blocks(&mut app, 1);
+        let err = app.execute(Addr::unchecked("attacker"), cw20_contract.call(deposit_for.clone()).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::TooManyLots { max: 40 }));
+
+        // the owner keeps room for their own deposits and can still leave
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        assert_eq!(lots(&app).len(), 41);
+        advance_blocks(&mut app, 20);
+        let msg = ExecuteMsg::WithdrawCw20 { address: cw20_contract.addr().to_string(), amount: Uint128::new(2), recipient: None };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(lots(&app).len(), 40);
+        let msg = ExecuteMsg::WithdrawAllCw20 { contract: cw20_contract.addr().to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert!(lots(&app).is_empty());
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9941));
+    }
+
+    #[test]
+    fn ibc_and_factory_denoms() {
+        let (mut app, deposit_id, _) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        const IBC_DENOM: &str = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+        const FACTORY_DENOM: &str = "factory/creator/utoken";
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: USER.to_string(),
+            amount: vec![coin(500, IBC_DENOM), coin(500, FACTORY_DENOM)],
+        }))
+        .unwrap();
+
+        for denom in ["ibc/27394FB0", "factory/creator", "factory//utoken"] {
+            let msg = ExecuteMsg::AddAllowedDenom { denom: denom.to_string(), min_deposit: None, max_user_balance: None, global_cap: None };
+            let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+            assert!(matches!(err.downcast().unwrap(), ContractError::InvalidDenom { .. }));
+        }
+        allow_denom(&mut app, &deposit_contract, IBC_DENOM);
+        allow_denom(&mut app, &deposit_contract, FACTORY_DENOM);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        let denom_info = |app: &App, denom: &str| -> DenomInfoResponse {
+            app.wrap()
+                .query_wasm_smart(deposit_contract.addr(), &QueryMsg::DenomInfo { denom: denom.to_string() })
+                .unwrap()
+        };
+        assert_eq!(denom_info(&app, NATIVE_DENOM).kind, DenomKind::Native {});
+        assert_eq!(denom_info(&app, FACTORY_DENOM).kind, DenomKind::TokenFactory { creator: "creator".to_string(), subdenom: "utoken".to_string() });
+        let info = denom_info(&app, IBC_DENOM);
+        assert_eq!(info.kind, DenomKind::Ibc { hash: IBC_DENOM[4..].to_string() });
+        assert_eq!(info.trace, None);
+
+        // the admin records where ibc denoms come from
+        let trace = IbcTrace { path: "transfer/channel-0".to_string(), base_denom: "uatom".to_string() };
+        let msg = ExecuteMsg::SetIbcTrace { denom: IBC_DENOM.to_string(), trace: Some(trace.clone()) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg.clone(), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(denom_info(&app, IBC_DENOM).trace, Some(trace.clone()));
+        let msg = ExecuteMsg::SetIbcTrace { denom: FACTORY_DENOM.to_string(), trace: Some(trace) };
+        let err = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NotIbcDenom { .. }));
+
+        let funds = vec![coin(200, NATIVE_DENOM), coin(300, IBC_DENOM), coin(400, FACTORY_DENOM)];
+        app.execute(Addr::unchecked(USER), deposit_contract.call(ExecuteMsg::Deposit {}, funds).unwrap()).unwrap();
+        assert_eq!(get_deposits(&app, &deposit_contract).deposits.len(), 3);
+        let msg = ExecuteMsg::WithdrawAll { denom: IBC_DENOM.to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), IBC_DENOM.to_string()).amount, Uint128::new(500));
+
+        let msg = ExecuteMsg::SetIbcTrace { denom: IBC_DENOM.to_string(), trace: None };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg, vec![]).unwrap()).unwrap();
+        assert_eq!(denom_info(&app, IBC_DENOM).trace, None);
+    }
+
+    #[test]
+    fn generic_withdraw_and_balances() {
+        let (mut app, deposit_id, cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        allow_cw20(&mut app, &deposit_contract, &cw20_contract);
+        allow_denom(&mut app, &deposit_contract, NATIVE_DENOM);
+
+        let cosmos_msg = deposit_contract.call(ExecuteMsg::Deposit { }, vec![coin(300, NATIVE_DENOM)]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        send_cw20(&mut app, &cw20_contract, &deposit_contract, 500);
+        advance_blocks(&mut app, 20);
+
+        // cw20 balances come before native ones
+        let res: BalancesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Balances { address: USER.to_string(), start_after: None, limit: None })
+            .unwrap();
+        let cw20_asset = AssetInfo::Cw20 { contract: cw20_contract.addr().to_string() };
+        let native_asset = AssetInfo::Native { denom: NATIVE_DENOM.to_string() };
+        let assets: Vec<_> = res.balances.iter().map(|balance| (balance.asset.clone(), balance.amount)).collect();
+        assert_eq!(assets, vec![(cw20_asset.clone(), Uint128::new(500)), (native_asset.clone(), Uint128::new(300))]);
+        let res: BalancesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Balances { address: USER.to_string(), start_after: Some(cw20_asset.clone()), limit: None })
+            .unwrap();
+        assert_eq!(res.balances.len(), 1);
+        assert_eq!(res.balances[0].asset, native_asset);
+
+        let msg = ExecuteMsg::Withdraw { asset: Some(cw20_asset.clone()), denom: None, amount: Uint128::new(200), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_cw20_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::new(9700));
+
+        let msg = ExecuteMsg::Withdraw { asset: Some(native_asset.clone()), denom: None, amount: Uint128::new(100), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(800));
+
+        // exactly one of asset and denom
+        let msg = ExecuteMsg::Withdraw { asset: Some(native_asset), denom: Some(NATIVE_DENOM.to_string()), amount: Uint128::new(100), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidAsset {}));
+        let msg = ExecuteMsg::Withdraw { asset: None, denom: None, amount: Uint128::new(100), recipient: None };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InvalidAsset {}));
+
+        // the old native withdraw message, with its amount as a string, is still accepted
+        let cosmos_msg = WasmMsg::Execute {
+            contract_addr: deposit_contract.addr().to_string(),
+            msg: Binary::from(br#"{"withdraw":{"amount":"100","denom":"denom"}}"#.as_slice()),
+            funds: vec![],
+        };
+        app.execute(Addr::unchecked(USER), cosmos_msg.into()).unwrap();
+        assert_eq!(get_balance(&app, USER.to_string(), NATIVE_DENOM.to_string()).amount, Uint128::new(900));
+
+        let res = get_deposits(&app, &deposit_contract);
+        assert_eq!(res.deposits[0].1.coins, coin(100, NATIVE_DENOM));
+        assert_eq!(res.deposits[0].1.withdraw_count, 2);
+        let res = get_cw20_deposits(&app, &deposit_contract);
+        assert_eq!(res.deposits[0].1.amount, Uint128::new(300));
+    }
+
+    #[test]
+    fn cw1155_deposits_lock_and_withdraw() {
+        let (mut app, deposit_id, _cw20_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw1155_id = app.store_code(contract_mock_cw1155());
+        let cw1155 = app
+            .instantiate_contract(cw1155_id, Addr::unchecked(ADMIN), &Empty {}, &[], "cw1155", None)
+            .unwrap();
+        for token_id in ["gold", "silver"] {
+            let msg = MockCw1155ExecuteMsg::Mint { to: USER.to_string(), token_id: token_id.to_string(), value: Uint128::new(100) };
+            app.execute_contract(Addr::unchecked(ADMIN), cw1155.clone(), &msg, &[]).unwrap();
+        }
+        let token_balance = |app: &App, owner: &str, token_id: &str| -> Uint128 {
+            app.wrap()
+                .query_wasm_smart(&cw1155, &MockCw1155QueryMsg::Balance { owner: owner.to_string(), token_id: token_id.to_string() })
+                .unwrap()
+        };
+        let deposit_balance = |app: &App, owner: &str, token_id: &str| -> Cw1155BalanceResponse {
+            let msg = QueryMsg::Cw1155Balance { address: owner.to_string(), contract: cw1155.to_string(), token_id: token_id.to_string() };
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &msg).unwrap()
+        };
+
+        let hook_msg = Cw1155HookMsg::Deposit { lock_tier: None };
+        let msg = MockCw1155ExecuteMsg::SendFrom {
+            from: USER.to_string(),
+            to: deposit_contract.addr().to_string(),
+            token_id: "gold".to_string(),
+            value: Uint128::new(40),
+            msg: Some(to_binary(&hook_msg).unwrap()),
+        };
+        // only allowed contracts can book deposits
+        let err = app.execute_contract(Addr::unchecked(USER), cw1155.clone(), &msg, &[]).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Cw1155NotAllowed { .. }));
+        let allow = ExecuteMsg::AddAllowedCw1155 { contract: cw1155.to_string() };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(allow.clone(), vec![]).unwrap()).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized {}));
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(allow, vec![]).unwrap()).unwrap();
+        let allowed: AllowedCw1155sResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllowedCw1155s { start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(allowed.contracts, vec![cw1155.clone()]);
+
+        let res = app.execute_contract(Addr::unchecked(USER), cw1155.clone(), &msg, &[]).unwrap();
+        assert!(res.has_event(&Event::new("wasm").add_attribute("execute", "cw1155_deposit").add_attribute("token_id", "gold")));
+        assert_eq!(token_balance(&app, deposit_contract.addr().as_str(), "gold"), Uint128::new(40));
+        assert_eq!(deposit_balance(&app, USER, "gold"), Cw1155BalanceResponse { locked: Uint128::new(40), withdrawable: Uint128::zero() });
+
+        // every token id of a batch gets its own lot
+        let hook_msg = Cw1155HookMsg::DepositFor { beneficiary: "friend".to_string(), memo: Some("gift".to_string()), lock_tier: None };
+        let msg = MockCw1155ExecuteMsg::BatchSendFrom {
+            from: USER.to_string(),
+            to: deposit_contract.addr().to_string(),
+            batch: vec![("gold".to_string(), Uint128::new(10)), ("silver".to_string(), Uint128::new(50))],
+            msg: Some(to_binary(&hook_msg).unwrap()),
+        };
+        app.execute_contract(Addr::unchecked(USER), cw1155.clone(), &msg, &[]).unwrap();
+        assert_eq!(deposit_balance(&app, "friend", "gold").locked, Uint128::new(10));
+        let lots: Cw1155LotsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw1155Lots { address: "friend".to_string(), contract: cw1155.to_string(), token_id: "silver".to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(lots.lots.len(), 1);
+        assert_eq!(lots.lots[0].amount, Uint128::new(50));
+        assert_eq!(lots.lots[0].memo, Some("gift".to_string()));
+
+        let withdraw = |token_id: &str, amount: u128| ExecuteMsg::WithdrawCw1155 {
+            contract: cw1155.to_string(),
+            token_id: token_id.to_string(),
+            amount: Uint128::new(amount),
+            recipient: None,
+        };
+        let cosmos_msg = deposit_contract.call(withdraw("gold", 30), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::StakeDurationNotPassed {}));
+
+        advance_blocks(&mut app, 20);
+        let cosmos_msg = deposit_contract.call(withdraw("gold", 30), vec![]).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        assert_eq!(token_balance(&app, USER, "gold"), Uint128::new(80));
+        assert_eq!(deposit_balance(&app, USER, "gold"), Cw1155BalanceResponse { locked: Uint128::zero(), withdrawable: Uint128::new(10) });
+
+        let cosmos_msg = deposit_contract.call(withdraw("gold", 20), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::InsufficientFunds { .. }));
+        let cosmos_msg = deposit_contract.call(withdraw("silver", 20), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::NoCw1155ToWithdraw {}));
 
+        // cw1155 withdraws are paused along with cw20 withdraws
+        let msg = ExecuteMsg::SetPause { native_deposit: None, native_withdraw: None, cw20_deposit: None, cw20_withdraw: Some(true) };
+        let cosmos_msg = deposit_contract.call(msg, vec![]).unwrap();
+        app.execute(Addr::unchecked(ADMIN), cosmos_msg).unwrap();
+        let cosmos_msg = deposit_contract.call(withdraw("gold", 10), vec![]).unwrap();
+        let err = app.execute(Addr::unchecked(USER), cosmos_msg).unwrap_err();
+        assert!(matches!(err.downcast().unwrap(), ContractError::Paused { operation } if operation == "cw20_withdraw"));
     }
-   
 }
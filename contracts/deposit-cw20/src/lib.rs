@@ -1,5 +1,8 @@
 pub mod contract;
+mod denoms;
 mod error;
+mod migrations;
+mod rewards;
 pub mod msg;
 pub mod state;
 pub mod helpers;
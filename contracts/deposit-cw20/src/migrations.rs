@@ -0,0 +1,576 @@
+use cosmwasm_std::{Addr, Coin, DepsMut, Env, Order, StdResult, Timestamp, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::{Duration, Expiration};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ContractError;
+use crate::state::{BlockStamp, EarlyWithdrawPolicy};
+
+/// Config before 0.6.0, without the early withdraw policy.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct Config {
+    admin: Addr,
+    pending_admin: Option<Addr>,
+    lock_duration: Duration,
+}
+
+const CONFIG: Item<Config> = Item::new("config");
+
+/// Config from 0.6.0, before the unbonding period.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct EarlyWithdrawConfig {
+    admin: Addr,
+    pending_admin: Option<Addr>,
+    lock_duration: Duration,
+    early_withdraw: Option<EarlyWithdrawPolicy>,
+}
+
+const EARLY_WITHDRAW_CONFIG: Item<EarlyWithdrawConfig> = Item::new("config");
+
+/// Lots before 0.6.0, which only kept the time of the deposit.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct Cw20Lot {
+    id: u64,
+    amount: Uint128,
+    deposited_at: Timestamp,
+    unlocks_at: Expiration,
+}
+
+const CW20_LOTS: Map<(&Addr, &Addr, u64), Cw20Lot> = Map::new("cw20lots");
+
+/// Lots from 0.6.0, before lock tiers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct StampedCw20Lot {
+    id: u64,
+    amount: Uint128,
+    deposited_at: BlockStamp,
+    unlocks_at: Expiration,
+}
+
+const STAMPED_CW20_LOTS: Map<(&Addr, &Addr, u64), StampedCw20Lot> = Map::new("cw20lots");
+
+/// Lots from 0.8.0, before memos.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct TieredCw20Lot {
+    id: u64,
+    amount: Uint128,
+    deposited_at: BlockStamp,
+    unlocks_at: Expiration,
+    lock_tier: Option<String>,
+    weight: Uint128,
+}
+
+const TIERED_CW20_LOTS: Map<(&Addr, &Addr, u64), TieredCw20Lot> = Map::new("cw20lots");
+
+/// Unbonding claims before 0.12.0, kept in one list per owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct ListedCw20Claim {
+    contract: Addr,
+    amount: Uint128,
+    release_at: Expiration,
+}
+
+const LISTED_CW20_CLAIMS: Map<&Addr, Vec<ListedCw20Claim>> = Map::new("cw20claims");
+
+/// Deposit records before 0.5.0, with a single count that went down on withdraws.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct Deposits {
+    count: i32,
+    owner: Addr,
+    coins: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct Cw20Deposits {
+    count: i32,
+    owner: Addr,
+    contract: Addr,
+    amount: Uint128,
+}
+
+// primary storage of the deposits, without the indexes added in 0.4.0
+const DEPOSITS: Map<(&str, &str), Deposits> = Map::new("deposits");
+const CW20_DEPOSITS: Map<(&str, &str), Cw20Deposits> = Map::new("cw20deposits");
+
+/// Deposit records from 0.5.0, before native and cw20 balances were merged.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct CountedDeposits {
+    deposit_count: u64,
+    withdraw_count: u64,
+    last_deposit: Option<BlockStamp>,
+    last_withdraw: Option<BlockStamp>,
+    owner: Addr,
+    coins: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct CountedCw20Deposits {
+    deposit_count: u64,
+    withdraw_count: u64,
+    last_deposit: Option<BlockStamp>,
+    last_withdraw: Option<BlockStamp>,
+    owner: Addr,
+    contract: Addr,
+    amount: Uint128,
+}
+
+struct CountedDepositIndexes<'a> {
+    denom: MultiIndex<'a, (String, u128), CountedDeposits, (Addr, String)>,
+}
+
+impl<'a> IndexList<CountedDeposits> for CountedDepositIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<CountedDeposits>> + '_> {
+        let v: Vec<&dyn Index<CountedDeposits>> = vec![&self.denom];
+        Box::new(v.into_iter())
+    }
+}
+
+fn counted_deposits<'a>() -> IndexedMap<'a, (&'a Addr, &'a str), CountedDeposits, CountedDepositIndexes<'a>> {
+    let indexes = CountedDepositIndexes {
+        denom: MultiIndex::new(
+            |_pk, d| (d.coins.denom.clone(), d.coins.amount.u128()),
+            "deposits",
+            "deposits__denom",
+        ),
+    };
+    IndexedMap::new("deposits", indexes)
+}
+
+struct CountedCw20DepositIndexes<'a> {
+    contract: MultiIndex<'a, (Addr, u128), CountedCw20Deposits, (Addr, Addr)>,
+}
+
+impl<'a> IndexList<CountedCw20Deposits> for CountedCw20DepositIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<CountedCw20Deposits>> + '_> {
+        let v: Vec<&dyn Index<CountedCw20Deposits>> = vec![&self.contract];
+        Box::new(v.into_iter())
+    }
+}
+
+fn counted_cw20_deposits<'a>(
+) -> IndexedMap<'a, (&'a Addr, &'a Addr), CountedCw20Deposits, CountedCw20DepositIndexes<'a>> {
+    let indexes = CountedCw20DepositIndexes {
+        contract: MultiIndex::new(
+            |_pk, d| (d.contract.clone(), d.amount.u128()),
+            "cw20deposits",
+            "cw20deposits__contract",
+        ),
+    };
+    IndexedMap::new("cw20deposits", indexes)
+}
+
+pub mod v0_2 {
+    use super::*;
+    use crate::state::{next_lot_id, PauseState, DENOM_TOTALS, PAUSED};
+
+    /// Lock every 0.1.0 cw20 deposit got.
+    const LEGACY_LOCK_DURATION: Duration = Duration::Height(20);
+
+    /// 0.1.0 kept a single stake time for the whole balance of a cw20.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    struct LegacyCw20Deposits {
+        count: i32,
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        stake_time: Expiration,
+    }
+
+    const LEGACY_CW20_DEPOSITS: Map<(&str, &str), LegacyCw20Deposits> = Map::new("cw20deposits");
+
+    /// Adds the config and pause state, turns each aggregate cw20 deposit into a single lot and
+    /// backfills the native totals. Allowlists start empty, so deposits stay closed until the
+    /// admin allows tokens again.
+    pub fn migrate(deps: DepsMut, env: &Env, admin: Option<String>) -> Result<(), ContractError> {
+        if CONFIG.may_load(deps.storage)?.is_none() {
+            let admin = admin.ok_or(ContractError::MissingAdmin {})?;
+            let config = Config {
+                admin: deps.api.addr_validate(&admin)?,
+                pending_admin: None,
+                lock_duration: LEGACY_LOCK_DURATION,
+            };
+            CONFIG.save(deps.storage, &config)?;
+        }
+        if PAUSED.may_load(deps.storage)?.is_none() {
+            PAUSED.save(deps.storage, &PauseState::default())?;
+        }
+
+        let legacy: Vec<LegacyCw20Deposits> = LEGACY_CW20_DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, deposit)| deposit))
+            .collect::<StdResult<_>>()?;
+        for deposit in legacy {
+            let key = (deposit.owner.as_str(), deposit.contract.as_str());
+            let (owner, contract) = (Addr::unchecked(key.0), Addr::unchecked(key.1));
+            if !deposit.amount.is_zero() {
+                let lot = Cw20Lot {
+                    id: next_lot_id(deps.storage)?,
+                    amount: deposit.amount,
+                    deposited_at: env.block.time,
+                    unlocks_at: deposit.stake_time,
+                };
+                CW20_LOTS.save(deps.storage, (&owner, &contract, lot.id), &lot)?;
+            }
+            let migrated = Cw20Deposits {
+                count: deposit.count,
+                owner,
+                contract,
+                amount: deposit.amount,
+            };
+            CW20_DEPOSITS.save(deps.storage, key, &migrated)?;
+        }
+
+        let deposits: Vec<_> = DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, deposit)| deposit.coins))
+            .collect::<StdResult<_>>()?;
+        for coins in deposits {
+            DENOM_TOTALS.update(deps.storage, &coins.denom, |total| -> StdResult<_> {
+                Ok(total.unwrap_or_default().checked_add(coins.amount)?)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_3 {
+    use super::*;
+    use crate::state::{CW20_TOTALS, DEPOSITORS};
+    use cosmwasm_std::Empty;
+
+    /// Builds the cw20 totals and the depositor list from the existing deposits.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let deposits: Vec<_> = CW20_DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, deposit)| deposit))
+            .collect::<StdResult<_>>()?;
+        for deposit in deposits {
+            DEPOSITORS.save(deps.storage, &deposit.owner, &Empty {})?;
+            CW20_TOTALS.update(deps.storage, &deposit.contract, |total| -> StdResult<_> {
+                Ok(total.unwrap_or_default().checked_add(deposit.amount)?)
+            })?;
+        }
+
+        let owners: Vec<_> = DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, deposit)| deposit.owner))
+            .collect::<StdResult<_>>()?;
+        for owner in owners {
+            DEPOSITORS.save(deps.storage, &owner, &Empty {})?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_4 {
+    use super::*;
+    use cw_storage_plus::PrimaryKey;
+
+    /// Writes the holder index entry of every deposit. Index entries only point at the primary
+    /// key, so they stay valid when later releases change the record layout.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let denom_index: MultiIndex<(String, u128), Deposits, (Addr, String)> = MultiIndex::new(
+            |_pk, d| (d.coins.denom.clone(), d.coins.amount.u128()),
+            "deposits",
+            "deposits__denom",
+        );
+        let native: Vec<_> = DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, denom), deposit) in native {
+            let pk = (&Addr::unchecked(owner), denom.as_str()).joined_key();
+            denom_index.save(deps.storage, &pk, &deposit)?;
+        }
+
+        let contract_index: MultiIndex<(Addr, u128), Cw20Deposits, (Addr, Addr)> = MultiIndex::new(
+            |_pk, d| (d.contract.clone(), d.amount.u128()),
+            "cw20deposits",
+            "cw20deposits__contract",
+        );
+        let cw20: Vec<_> = CW20_DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract), deposit) in cw20 {
+            let pk = (&Addr::unchecked(owner), &Addr::unchecked(contract)).joined_key();
+            contract_index.save(deps.storage, &pk, &deposit)?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_5 {
+    use super::*;
+
+    /// Splits the count into deposit and withdraw counters and drops the records left at zero by
+    /// full withdraws. The old count was net of withdraws, so it becomes the deposit count and
+    /// the history before the migration is not recorded.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let native: Vec<_> = DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, denom), legacy) in native {
+            let owner = Addr::unchecked(owner);
+            let deposit = CountedDeposits {
+                deposit_count: legacy.count.max(0) as u64,
+                withdraw_count: 0,
+                last_deposit: None,
+                last_withdraw: None,
+                owner: legacy.owner,
+                coins: legacy.coins,
+            };
+            //the index key does not change, so the old entry is only removed with the record
+            if deposit.coins.amount.is_zero() {
+                counted_deposits().replace(deps.storage, (&owner, &denom), None, Some(&deposit))?;
+            } else {
+                counted_deposits().replace(deps.storage, (&owner, &denom), Some(&deposit), None)?;
+            }
+        }
+
+        let cw20: Vec<_> = CW20_DEPOSITS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract), legacy) in cw20 {
+            let (owner, contract) = (Addr::unchecked(owner), Addr::unchecked(contract));
+            let deposit = CountedCw20Deposits {
+                deposit_count: legacy.count.max(0) as u64,
+                withdraw_count: 0,
+                last_deposit: None,
+                last_withdraw: None,
+                owner: legacy.owner,
+                contract: legacy.contract,
+                amount: legacy.amount,
+            };
+            if deposit.amount.is_zero() {
+                counted_cw20_deposits().replace(deps.storage, (&owner, &contract), None, Some(&deposit))?;
+            } else {
+                counted_cw20_deposits().replace(deps.storage, (&owner, &contract), Some(&deposit), None)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_6 {
+    use super::*;
+
+    /// Adds the early withdraw policy, disabled, and the height of the deposit to the lots. The
+    /// height of older deposits is unknown, so they count as made at the migration height.
+    pub fn migrate(deps: DepsMut, env: &Env) -> Result<(), ContractError> {
+        let config = CONFIG.load(deps.storage)?;
+        let config = EarlyWithdrawConfig {
+            admin: config.admin,
+            pending_admin: config.pending_admin,
+            lock_duration: config.lock_duration,
+            early_withdraw: None,
+        };
+        EARLY_WITHDRAW_CONFIG.save(deps.storage, &config)?;
+
+        let lots: Vec<_> = CW20_LOTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract, id), lot) in lots {
+            let lot = StampedCw20Lot {
+                id: lot.id,
+                amount: lot.amount,
+                deposited_at: BlockStamp { height: env.block.height, time: lot.deposited_at },
+                unlocks_at: lot.unlocks_at,
+            };
+            STAMPED_CW20_LOTS.save(deps.storage, (&owner, &contract, id), &lot)?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_7 {
+    use super::*;
+    use crate::state;
+
+    /// Adds the unbonding period, disabled.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let config = EARLY_WITHDRAW_CONFIG.load(deps.storage)?;
+        let config = state::Config {
+            admin: config.admin,
+            pending_admin: config.pending_admin,
+            lock_duration: config.lock_duration,
+            early_withdraw: config.early_withdraw,
+            unbonding_period: None,
+        };
+        state::CONFIG.save(deps.storage, &config)?;
+        Ok(())
+    }
+}
+
+pub mod v0_8 {
+    use super::*;
+    use crate::state::{CW20_TOTALS, CW20_WEIGHTED_TOTALS, CW20_WEIGHTS};
+
+    /// Adds the lock tier and reward weight of the lots. Existing lots have the default lock, so
+    /// their weight is their amount, and the weights of the deposits and totals are the amounts.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let lots: Vec<_> = STAMPED_CW20_LOTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract, id), lot) in lots {
+            let lot = TieredCw20Lot {
+                id: lot.id,
+                amount: lot.amount,
+                deposited_at: lot.deposited_at,
+                unlocks_at: lot.unlocks_at,
+                lock_tier: None,
+                weight: lot.amount,
+            };
+            TIERED_CW20_LOTS.save(deps.storage, (&owner, &contract, id), &lot)?;
+        }
+
+        let deposits: Vec<_> = counted_cw20_deposits()
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, deposit)| deposit))
+            .collect::<StdResult<_>>()?;
+        for deposit in deposits {
+            CW20_WEIGHTS.save(deps.storage, (&deposit.owner, &deposit.contract), &deposit.amount)?;
+        }
+
+        let totals: Vec<_> = CW20_TOTALS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for (contract, total) in totals {
+            CW20_WEIGHTED_TOTALS.save(deps.storage, &contract, &total)?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_9 {
+    use super::*;
+    use crate::state;
+
+    /// Adds the memo of the lots, empty for existing lots.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let lots: Vec<_> = TIERED_CW20_LOTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract, id), lot) in lots {
+            let lot = state::Cw20Lot {
+                id: lot.id,
+                amount: lot.amount,
+                deposited_at: lot.deposited_at,
+                unlocks_at: lot.unlocks_at,
+                lock_tier: lot.lock_tier,
+                weight: lot.weight,
+                memo: None,
+            };
+            state::CW20_LOTS.save(deps.storage, (&owner, &contract, id), &lot)?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_10 {
+    use super::*;
+    use crate::denoms;
+    use crate::state::{ALLOWED_DENOMS, DENOM_KINDS};
+
+    /// Records the kind of the allowed denoms. Malformed ibc and token factory denoms are left
+    /// unrecorded, they cannot be allowed again in their current form.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let allowed: Vec<String> = ALLOWED_DENOMS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for denom in allowed {
+            if let Ok(kind) = denoms::classify(&denom) {
+                DENOM_KINDS.save(deps.storage, &denom, &kind)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_11 {
+    use super::*;
+    use crate::msg::AssetInfo;
+    use crate::state::{balance_key, balances, Balance};
+
+    /// Moves the native and cw20 deposit records into the balances keyed by asset. Removing the
+    /// old records through their indexed maps also drops their holder index entries.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let native: Vec<_> = counted_deposits()
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, denom), deposit) in native {
+            let balance = Balance {
+                owner: deposit.owner,
+                asset: AssetInfo::Native { denom: deposit.coins.denom },
+                amount: deposit.coins.amount,
+                deposit_count: deposit.deposit_count,
+                withdraw_count: deposit.withdraw_count,
+                last_deposit: deposit.last_deposit,
+                last_withdraw: deposit.last_withdraw,
+            };
+            let (kind, id) = balance_key(&balance.asset);
+            balances().save(deps.storage, (&owner, kind, id), &balance)?;
+            counted_deposits().remove(deps.storage, (&owner, &denom))?;
+        }
+
+        let cw20: Vec<_> = counted_cw20_deposits()
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract), deposit) in cw20 {
+            let balance = Balance {
+                owner: deposit.owner,
+                asset: AssetInfo::Cw20 { contract: deposit.contract.to_string() },
+                amount: deposit.amount,
+                deposit_count: deposit.deposit_count,
+                withdraw_count: deposit.withdraw_count,
+                last_deposit: deposit.last_deposit,
+                last_withdraw: deposit.last_withdraw,
+            };
+            let (kind, id) = balance_key(&balance.asset);
+            balances().save(deps.storage, (&owner, kind, id), &balance)?;
+            counted_cw20_deposits().remove(deps.storage, (&owner, &contract))?;
+        }
+        Ok(())
+    }
+}
+
+pub mod v0_12 {
+    use super::*;
+    use crate::rewards;
+    use crate::state::{
+        next_claim_id, Cw20Claim, CW20_CLAIMS, CW20_UNBONDING_TOTALS, CW20_WEIGHTS, PENALTY_INDEX, UNCLAIMED_PENALTIES,
+        USER_PENALTIES,
+    };
+
+    /// Gives every unbonding claim an id and keys it by owner and id, keeping the order of the
+    /// lists, and sums the claims of each cw20 into its unbonding total. The unclaimed penalties
+    /// start from the shares depositors have earned so far.
+    pub fn migrate(deps: DepsMut) -> Result<(), ContractError> {
+        let listed: Vec<_> = LISTED_CW20_CLAIMS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for (owner, claims) in listed {
+            for claim in claims {
+                let id = next_claim_id(deps.storage)?;
+                let claim = Cw20Claim { id, contract: claim.contract, amount: claim.amount, release_at: claim.release_at };
+                CW20_UNBONDING_TOTALS.update(deps.storage, &claim.contract, |total| -> StdResult<_> {
+                    Ok(total.unwrap_or_default().checked_add(claim.amount)?)
+                })?;
+                CW20_CLAIMS.save(deps.storage, (&owner, id), &claim)?;
+            }
+            LISTED_CW20_CLAIMS.remove(deps.storage, &owner);
+        }
+
+        let shares: Vec<_> = USER_PENALTIES
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for ((owner, contract), share) in shares {
+            let index = PENALTY_INDEX.may_load(deps.storage, &contract)?.unwrap_or_default();
+            let staked = CW20_WEIGHTS.may_load(deps.storage, (&owner, &contract))?.unwrap_or_default();
+            let earned = rewards::earned(&share, index, staked)?;
+            UNCLAIMED_PENALTIES.update(deps.storage, &contract, |total| -> StdResult<_> {
+                Ok(total.unwrap_or_default().checked_add(earned)?)
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -1,30 +1,219 @@
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use cw20::Cw20ReceiveMsg;
+use cw_utils::{Duration, Expiration};
 
-use crate::state::{Cw20Deposits, Deposits};
+use crate::state::{Balance, BlockStamp, Cw1155Lot, Cw20Claim, Cw20Lot, DenomKind, EarlyWithdrawPolicy, IbcTrace};
 
+/// A native denom or a cw20 contract held by the contract.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct InstantiateMsg {
+pub enum AssetInfo {
+    Native { denom: String },
+    Cw20 { contract: String },
+}
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InstantiateMsg {
+    /// defaults to the instantiating address
+    pub admin: Option<String>,
+    /// lock applied to cw20 deposits, unless overridden for the cw20 contract
+    pub lock_duration: Duration,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Deposit { },
-    Withdraw { amount:u128, denom:String },
-    Receive(Cw20ReceiveMsg),
-    WithdrawCw20 { address: String, amount:Uint128 },
+    /// Credits the attached coins to `beneficiary` instead of the sender. The memo is echoed in
+    /// the events.
+    DepositFor { beneficiary: String, memo: Option<String> },
+    /// Withdraws `amount` of `asset`, paying `recipient`, or the sender when not set. `denom` is
+    /// the older form of a native `asset`, exactly one of them has to be set. Cw20 withdraws take
+    /// matured lots only.
+    Withdraw { asset: Option<AssetInfo>, denom: Option<String>, amount: Uint128, recipient: Option<String> },
+    /// Cw20 and single cw1155 transfers to the contract. Cw1155 deposits are paused along with
+    /// cw20 deposits.
+    Receive(ReceiveMsg),
+    /// Batch cw1155 transfer to the contract, each token id gets its own lot.
+    BatchReceive(Cw1155BatchReceiveMsg),
+    /// Withdraws matured lots of a cw1155 token id. Paused along with cw20 withdraws, unbonding
+    /// only applies to cw20.
+    WithdrawCw1155 { contract: String, token_id: String, amount: Uint128, recipient: Option<String> },
+    /// Same as `Withdraw` with a cw20 `asset`.
+    WithdrawCw20 { address: String, amount:Uint128, recipient: Option<String> },
+    /// Withdraws the whole native balance of a denom.
+    WithdrawAll { denom: String },
+    /// Withdraws every matured lot of a cw20 contract.
+    WithdrawAllCw20 { contract: String },
+    /// Withdraws cw20 before the lots unlock, when the config allows it. Matured lots are taken
+    /// first, then locked lots oldest first with a penalty decaying linearly to zero at unlock.
+    EarlyWithdrawCw20 { contract: String, amount: Uint128 },
+    /// Admin only. `None` disables early withdraws.
+    SetEarlyWithdrawPolicy { policy: Option<EarlyWithdrawPolicy> },
+    /// Moves cw20 out of the deposit into a claim released after the unbonding period, oldest
+    /// lots first. The unbonding period replaces the default lock, so those lots are taken while
+    /// locked. Lots of a lock tier are only taken once matured.
+    UnbondCw20 { contract: String, amount: Uint128 },
+    /// Pays every released claim of the sender.
+    ClaimCw20 {},
+    /// Admin only. While set, cw20 can only leave through `UnbondCw20` and `ClaimCw20`.
+    /// `None` disables unbonding.
+    SetUnbondingPeriod { period: Option<Duration> },
+    /// Withdraws every native balance and every matured cw20 lot.
+    WithdrawEverything {},
+    /// Moves part of the native balance of the sender to another depositor, without sending coins.
+    TransferDeposit { recipient: String, denom: String, amount: Uint128 },
+    /// Moves cw20 lots of the sender to another depositor, oldest first. The lots keep their
+    /// lock, so locked tokens can be transferred but not withdrawn sooner. They join a lot of the
+    /// recipient with the same lock, and are limited like deposits for others otherwise.
+    TransferCw20Deposit { recipient: String, contract: String, amount: Uint128 },
+    /// Lets `spender` withdraw up to `amount` more of the deposit of the sender. `expires`
+    /// replaces the current expiration when set.
+    IncreaseDepositAllowance { spender: String, asset: AssetInfo, amount: Uint128, expires: Option<Expiration> },
+    /// Lowers an allowance, removing it once it reaches zero.
+    DecreaseDepositAllowance { spender: String, asset: AssetInfo, amount: Uint128, expires: Option<Expiration> },
+    /// Withdraws from the deposit of `owner` using an allowance. Pays `recipient`, or the sender
+    /// when not set.
+    WithdrawFrom { owner: String, asset: AssetInfo, amount: Uint128, recipient: Option<String> },
+    /// Admin only. Creates the reward pool of a deposited cw20, or changes its emission rate.
+    /// The reward asset of an existing pool cannot change.
+    SetRewardPool { contract: String, reward: AssetInfo, emission_per_second: Uint128 },
+    /// Admin only. Adds the attached coins to the reward pool of a deposited cw20. Cw20 rewards
+    /// are funded with `Cw20HookMsg::FundRewards`.
+    FundRewards { contract: String },
+    /// Pays the rewards of the sender for every cw20 they deposited. Fails while withdraws of
+    /// the kind of a reward to pay are paused.
+    ClaimRewards {},
+    /// Books the rewards of `address` for a cw20 and drops the weight of its matured tier lots
+    /// back to their amount. Owners are reweighted whenever their deposit changes, anyone can
+    /// call this for an idle one.
+    ReweightCw20 { address: String, contract: String },
+    /// Admin only.
+    UpdateConfig { lock_duration: Option<Duration> },
+    /// Admin only. Sets the lock for a single cw20 contract, `None` falls back to the default.
+    SetCw20LockDuration { contract: String, lock_duration: Option<Duration> },
+    /// Admin only. Creates a lock tier, or updates it. Lots keep the lock and multiplier they
+    /// were deposited with, the multiplier applies until the lot matures. It has to be above
+    /// zero and at most 100.
+    SetLockTier { name: String, duration: Duration, reward_multiplier: Decimal },
+    /// Admin only.
+    RemoveLockTier { name: String },
+    /// Admin only. The new admin has to accept before the handover takes place.
+    ProposeNewAdmin { admin: String },
+    /// Called by the proposed admin.
+    AcceptAdmin {},
+    /// Admin only. Allows deposits of a cw20 contract, or updates its limits when already allowed.
+    AddAllowedToken { contract: String, min_deposit: Option<Uint128>, max_deposit: Option<Uint128> },
+    /// Admin only. Existing deposits of the token can still be withdrawn.
+    RemoveAllowedToken { contract: String },
+    /// Admin only. Allows deposits of every token id of a cw1155 contract.
+    AddAllowedCw1155 { contract: String },
+    /// Admin only. Existing deposits of the contract can still be withdrawn.
+    RemoveAllowedCw1155 { contract: String },
+    /// Admin only. Allows native deposits of a denom, or updates its limits when already allowed.
+    AddAllowedDenom {
+        denom: String,
+        min_deposit: Option<Uint128>,
+        max_user_balance: Option<Uint128>,
+        global_cap: Option<Uint128>,
+    },
+    /// Admin only. Existing deposits of the denom can still be withdrawn.
+    RemoveAllowedDenom { denom: String },
+    /// Admin only. Records the origin of an ibc denom, `None` removes it.
+    SetIbcTrace { denom: String, trace: Option<IbcTrace> },
+    /// Admin only. Switches left as `None` keep their current value.
+    SetPause {
+        native_deposit: Option<bool>,
+        native_withdraw: Option<bool>,
+        cw20_deposit: Option<bool>,
+        cw20_withdraw: Option<bool>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    Deposits { address: String },
-    Cw20Deposits { address: String },
+    /// Balances of an address in every asset, cw20 first, then ordered by denom or address.
+    Balances { address: String, start_after: Option<AssetInfo>, limit: Option<u32> },
+    /// Native deposits of an address, ordered by denom.
+    Deposits { address: String, start_after: Option<String>, limit: Option<u32> },
+    /// Cw20 deposits of an address, ordered by cw20 contract address.
+    Cw20Deposits { address: String, start_after: Option<String>, limit: Option<u32> },
+    /// Locked and withdrawable amounts of an owner for one cw20 contract.
+    Cw20Balance { address: String, contract: String },
+    /// Lots of an owner for one cw20 contract, oldest first. `start_after` is a lot id.
+    Cw20Lots { address: String, contract: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Locked and withdrawable amounts of an owner for one cw1155 token id.
+    Cw1155Balance { address: String, contract: String, token_id: String },
+    /// Lots of an owner for one cw1155 token id, oldest first. `start_after` is a lot id.
+    Cw1155Lots { address: String, contract: String, token_id: String, start_after: Option<u64>, limit: Option<u32> },
+    Config {},
+    /// Returns the `PauseState`.
+    PauseStatus {},
+    /// Every address that has deposited, ordered by address.
+    AllDepositors { start_after: Option<String>, limit: Option<u32> },
+    /// Sum of the native deposits of every user for a denom.
+    DenomTotal { denom: String },
+    /// Sum of the deposits of every user for a cw20 contract, and of the cw20 the contract still
+    /// owes outside of deposits.
+    Cw20Total { contract: String },
+    AllDenomTotals { start_after: Option<String>, limit: Option<u32> },
+    /// Holders of a denom, largest deposit first. `start_after` is the last holder of the previous page.
+    DenomHolders { denom: String, start_after: Option<Holder>, limit: Option<u32> },
+    /// Holders of a cw20, largest deposit first. `start_after` is the last holder of the previous page.
+    Cw20Holders { contract: String, start_after: Option<Holder>, limit: Option<u32> },
+    AllowedTokens { start_after: Option<String>, limit: Option<u32> },
+    /// Cw1155 contracts that can be deposited, ordered by address.
+    AllowedCw1155s { start_after: Option<String>, limit: Option<u32> },
+    AllowedDenoms { start_after: Option<String>, limit: Option<u32> },
+    /// Kind of a denom, with the origin of ibc denoms when the admin has set it.
+    DenomInfo { denom: String },
+    /// Lock tiers depositors can choose from, ordered by name.
+    LockTiers { start_after: Option<String>, limit: Option<u32> },
+    /// Lock that applies to new deposits of the given cw20 contract.
+    EffectiveLockDuration { contract: String },
+    /// Zero and never expiring when there is no allowance.
+    Allowance { owner: String, spender: String, asset: AssetInfo },
+    /// Allowances granted by an owner, ordered by spender and asset.
+    AllAllowances { owner: String, start_after: Option<AllowanceKey>, limit: Option<u32> },
+    /// Returns the `RewardPool` of a deposited cw20, brought up to the current block.
+    RewardPool { contract: String },
+    /// Unclaimed rewards of an address, per deposited cw20.
+    PendingRewards { address: String },
+    /// Unbonding claims of an address, released or not, oldest first. `start_after` is a claim id.
+    Claims { address: String, start_after: Option<u64>, limit: Option<u32> },
+}
+
+/// Native balance in the layout of the `Deposits` query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Deposits {
+    pub deposit_count: u64,
+    pub withdraw_count: u64,
+    pub last_deposit: Option<BlockStamp>,
+    pub last_withdraw: Option<BlockStamp>,
+    pub owner: Addr,
+    pub coins: Coin
+}
+
+/// Cw20 balance in the layout of the `Cw20Deposits` query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20Deposits {
+    pub deposit_count: u64,
+    pub withdraw_count: u64,
+    pub last_deposit: Option<BlockStamp>,
+    pub last_withdraw: Option<BlockStamp>,
+    pub owner: Addr,
+    pub contract: Addr,
+    pub amount:Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BalancesResponse {
+    pub balances: Vec<Balance>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -36,15 +225,271 @@ pub struct DepositResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Cw20DepositResponse {
-    pub deposits: Vec<(String, Cw20Deposits)>,
+    pub deposits: Vec<(Addr, Cw20Deposits)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw20BalanceResponse {
+    pub locked: Uint128,
+    pub withdrawable: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw20LotsResponse {
+    pub lots: Vec<Cw20Lot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw1155BalanceResponse {
+    pub locked: Uint128,
+    pub withdrawable: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw1155LotsResponse {
+    pub lots: Vec<Cw1155Lot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ConfigResponse {
+    pub admin: Addr,
+    pub pending_admin: Option<Addr>,
+    pub lock_duration: Duration,
+    pub early_withdraw: Option<EarlyWithdrawPolicy>,
+    pub unbonding_period: Option<Duration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DepositorsResponse {
+    pub depositors: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DenomTotalResponse {
+    pub denom: String,
+    pub total: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllDenomTotalsResponse {
+    pub totals: Vec<DenomTotalResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw20TotalResponse {
+    pub contract: Addr,
+    pub total: Uint128,
+    /// unbonded and not claimed yet
+    pub unbonding: Uint128,
+    /// early withdraw penalties shared with the depositors and not claimed yet
+    pub penalties: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Holder {
+    pub address: Addr,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct HoldersResponse {
+    pub holders: Vec<Holder>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowedToken {
+    pub contract: Addr,
+    pub min_deposit: Option<Uint128>,
+    pub max_deposit: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowedTokensResponse {
+    pub tokens: Vec<AllowedToken>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowedCw1155sResponse {
+    pub contracts: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowedDenom {
+    pub denom: String,
+    pub min_deposit: Option<Uint128>,
+    pub max_user_balance: Option<Uint128>,
+    pub global_cap: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowedDenomsResponse {
+    pub denoms: Vec<AllowedDenom>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DenomInfoResponse {
+    pub denom: String,
+    pub kind: DenomKind,
+    pub trace: Option<IbcTrace>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LockDurationResponse {
+    pub contract: Addr,
+    pub lock_duration: Duration,
+    /// true when the contract has its own lock instead of the default one
+    pub overridden: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LockTierInfo {
+    pub name: String,
+    pub duration: Duration,
+    pub reward_multiplier: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LockTiersResponse {
+    pub tiers: Vec<LockTierInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowanceResponse {
+    pub allowance: Uint128,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowanceKey {
+    pub spender: String,
+    pub asset: AssetInfo,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowanceInfo {
+    pub spender: Addr,
+    pub asset: AssetInfo,
+    pub allowance: Uint128,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AllAllowancesResponse {
+    pub allowances: Vec<AllowanceInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingReward {
+    /// deposited cw20 the rewards are paid for
+    pub contract: Addr,
+    pub reward: AssetInfo,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingRewardsResponse {
+    pub rewards: Vec<PendingReward>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum MigrateMsg {}
+pub struct ClaimsResponse {
+    pub claims: Vec<Cw20Claim>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    /// Admin to set when migrating from 0.1.0, which had no config. Ignored otherwise.
+    pub admin: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
-    Deposit { },
+    /// Locks the tokens for the duration of `lock_tier`, or for the lock of the cw20 when not set.
+    Deposit { lock_tier: Option<String> },
+    /// Like `Deposit`, credited to `beneficiary`. The memo is echoed in the events and stored on
+    /// the lot. An owner holds at most 50 lots of a cw20, deposits for others stop at 40.
+    DepositFor { beneficiary: String, memo: Option<String>, lock_tier: Option<String> },
+    /// Admin only. Adds the sent tokens to the reward pool of a deposited cw20.
+    FundRewards { contract: String },
+}
+
+/// Payload of `receive`, which cw20 and cw1155 share. Told apart by their fields: cw1155 sends
+/// an `operator` and a `token_id`, cw20 a `sender`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum ReceiveMsg {
+    Cw1155(Cw1155ReceiveMsg),
+    Cw20(Cw20ReceiveMsg),
+}
+
+/// Sent by a cw1155 contract for a single token transfer, as in the cw1155 spec.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw1155ReceiveMsg {
+    /// address that executed the transfer
+    pub operator: String,
+    /// previous owner of the tokens, `None` when they were minted
+    pub from: Option<String>,
+    pub token_id: String,
+    pub amount: Uint128,
+    /// a `Cw1155HookMsg`
+    pub msg: Binary,
+}
+
+/// Sent by a cw1155 contract for a batch transfer, as in the cw1155 spec.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw1155BatchReceiveMsg {
+    pub operator: String,
+    pub from: Option<String>,
+    /// token ids and amounts
+    pub batch: Vec<(String, Uint128)>,
+    pub msg: Binary,
+}
+
+/// Credited to the previous owner of the tokens, or to the operator for minted tokens.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw1155HookMsg {
+    /// Locks the tokens for the duration of `lock_tier`, or for the lock of the contract when not set.
+    Deposit { lock_tier: Option<String> },
+    /// Like `Deposit`, credited to `beneficiary`. The memo is echoed in the events and stored on
+    /// the lots. An owner holds at most 50 lots of a token id, deposits for others stop at 40.
+    DepositFor { beneficiary: String, memo: Option<String>, lock_tier: Option<String> },
+}
+
+/// Transfer message of the cw1155 spec, used to pay out withdraws.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw1155ExecuteMsg {
+    SendFrom { from: String, to: String, token_id: String, value: Uint128, msg: Option<Binary> },
 }
\ No newline at end of file
@@ -0,0 +1,203 @@
+//! Rewards of cw20 depositors. Stakes are weighted by the reward multiplier of the lock tier of
+//! each lot, so the stake of an owner and `total_staked` are weights rather than amounts. A lot
+//! weighs its amount again once it matures, from the next settlement of its owner.
+
+use std::convert::TryFrom;
+
+use cosmwasm_std::{
+    Addr, BlockInfo, Decimal256, Fraction, Order, OverflowError, OverflowOperation, StdError, StdResult, Storage,
+    Timestamp, Uint128, Uint256,
+};
+use cw_storage_plus::Map;
+
+use crate::msg::AssetInfo;
+use crate::state::{
+    balances, Cw20Lot, RewardPool, UserReward, CW20_LOTS, CW20_WEIGHTED_TOTALS, CW20_WEIGHTS, PENALTY_INDEX, REWARD_POOLS,
+    UNCLAIMED_PENALTIES, USER_PENALTIES, USER_REWARDS, CW20_KIND,
+};
+
+/// Emits the rewards of the pool since its last update over `total_staked`. Nothing is emitted
+/// while nothing is staked, and never more than the pool holds. The index is a `Decimal256`, so
+/// large emissions over a tiny stake still fit.
+pub fn accrue(pool: &mut RewardPool, now: Timestamp, total_staked: Uint128) -> StdResult<()> {
+    if now <= pool.last_update {
+        return Ok(());
+    }
+    if !total_staked.is_zero() {
+        let elapsed = Uint128::from(now.seconds() - pool.last_update.seconds());
+        let emitted = elapsed.checked_mul(pool.emission_per_second)?.min(pool.undistributed);
+        pool.reward_per_token = pool.reward_per_token.checked_add(per_token(emitted, total_staked)?)?;
+        pool.undistributed -= emitted;
+    }
+    pool.last_update = now;
+    Ok(())
+}
+
+/// Shares `amount` of penalties paid by `owner` between the other depositors of a cw20, by
+/// weight. The owner has to be settled. Returns false when there is nobody to share with.
+pub fn distribute_penalty(storage: &mut dyn Storage, contract: &Addr, owner: &Addr, amount: Uint128) -> StdResult<bool> {
+    let staked = staked(storage, owner, contract)?;
+    let others = total_weight(storage, contract)?.checked_sub(staked)?;
+    if others.is_zero() {
+        return Ok(false);
+    }
+    let index = PENALTY_INDEX
+        .may_load(storage, contract)?
+        .unwrap_or_default()
+        .checked_add(per_token(amount, others)?)?;
+    PENALTY_INDEX.save(storage, contract, &index)?;
+    UNCLAIMED_PENALTIES.update(storage, contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    //the remaining deposit of the owner takes no share of their own penalty
+    if !staked.is_zero() {
+        USER_PENALTIES.update(storage, (owner, contract), |reward| -> StdResult<_> {
+            Ok(UserReward { reward_per_token_paid: index, ..reward.unwrap_or_default() })
+        })?;
+    }
+    Ok(true)
+}
+
+/// `amount` spread over `total_staked`, to add to an index.
+fn per_token(amount: Uint128, total_staked: Uint128) -> StdResult<Decimal256> {
+    Decimal256::checked_from_ratio(amount, total_staked)
+        .map_err(|_| StdError::overflow(OverflowError::new(OverflowOperation::Mul, amount, total_staked)))
+}
+
+/// Rewards earned by `staked` since the owner was last settled.
+pub fn earned(reward: &UserReward, reward_per_token: Decimal256, staked: Uint128) -> StdResult<Uint128> {
+    let index = reward_per_token.checked_sub(reward.reward_per_token_paid)?;
+    let accrued = Uint256::from(staked)
+        .checked_multiply_ratio(index.numerator(), index.denominator())
+        .map_err(|_| StdError::overflow(OverflowError::new(OverflowOperation::Mul, staked, index)))?;
+    Ok(reward.pending.checked_add(Uint128::try_from(accrued)?)?)
+}
+
+/// Sum of the weights of every depositor of a cw20.
+pub fn total_weight(storage: &dyn Storage, contract: &Addr) -> StdResult<Uint128> {
+    Ok(CW20_WEIGHTED_TOTALS.may_load(storage, contract)?.unwrap_or_default())
+}
+
+fn staked(storage: &dyn Storage, owner: &Addr, contract: &Addr) -> StdResult<Uint128> {
+    Ok(CW20_WEIGHTS.may_load(storage, (owner, contract))?.unwrap_or_default())
+}
+
+fn settle_with(
+    storage: &mut dyn Storage,
+    rewards: &Map<(&Addr, &Addr), UserReward>,
+    owner: &Addr,
+    contract: &Addr,
+    reward_per_token: Decimal256,
+    staked: Uint128,
+) -> StdResult<()> {
+    let mut reward = rewards.may_load(storage, (owner, contract))?.unwrap_or_default();
+    reward.pending = earned(&reward, reward_per_token, staked)?;
+    reward.reward_per_token_paid = reward_per_token;
+    rewards.save(storage, (owner, contract), &reward)
+}
+
+/// Books the rewards and penalty shares of an owner up to `block`, then drops the weight of
+/// their matured lots back to the amount. Has to run before the stake of the owner or the total
+/// staked changes.
+pub fn settle(storage: &mut dyn Storage, block: &BlockInfo, owner: &Addr, contract: &Addr) -> StdResult<()> {
+    let staked = staked(storage, owner, contract)?;
+    if let Some(mut pool) = REWARD_POOLS.may_load(storage, contract)? {
+        let total_staked = total_weight(storage, contract)?;
+        accrue(&mut pool, block.time, total_staked)?;
+        REWARD_POOLS.save(storage, contract, &pool)?;
+        settle_with(storage, &USER_REWARDS, owner, contract, pool.reward_per_token, staked)?;
+    }
+    if let Some(index) = PENALTY_INDEX.may_load(storage, contract)? {
+        settle_with(storage, &USER_PENALTIES, owner, contract, index, staked)?;
+    }
+    reweight_matured(storage, block, owner, contract)
+}
+
+fn reweight_matured(storage: &mut dyn Storage, block: &BlockInfo, owner: &Addr, contract: &Addr) -> StdResult<()> {
+    let matured: Vec<Cw20Lot> = CW20_LOTS
+        .prefix((owner, contract))
+        .range(storage, None, None, Order::Ascending)
+        .map(|lot| lot.map(|(_, lot)| lot))
+        .filter(|lot| lot.as_ref().map_or(true, |lot| lot.weight != lot.amount && lot.unlocks_at.is_expired(block)))
+        .collect::<StdResult<_>>()?;
+    if matured.is_empty() {
+        return Ok(());
+    }
+
+    let (mut removed, mut added) = (Uint128::zero(), Uint128::zero());
+    for mut lot in matured {
+        removed = removed.checked_add(lot.weight)?;
+        added = added.checked_add(lot.amount)?;
+        lot.weight = lot.amount;
+        CW20_LOTS.save(storage, (owner, contract, lot.id), &lot)?;
+    }
+    CW20_WEIGHTS.update(storage, (owner, contract), |weight| -> StdResult<_> {
+        Ok(weight.unwrap_or_default().checked_sub(removed)?.checked_add(added)?)
+    })?;
+    CW20_WEIGHTED_TOTALS.update(storage, contract, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_sub(removed)?.checked_add(added)?)
+    })?;
+    Ok(())
+}
+
+/// Settles an owner and takes what they can claim for a cw20, as (asset, amount) pairs.
+/// Records of owners without a deposit left are removed once claimed.
+pub fn take_pending(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    contract: &Addr,
+) -> StdResult<Vec<(AssetInfo, Uint128)>> {
+    settle(storage, block, owner, contract)?;
+    let has_deposit = balances().has(storage, (owner, CW20_KIND, contract.as_str()));
+
+    let mut claims = vec![];
+    if let Some(pool) = REWARD_POOLS.may_load(storage, contract)? {
+        claims.push((pool.reward, take(storage, &USER_REWARDS, owner, contract, has_deposit)?));
+    }
+    if PENALTY_INDEX.has(storage, contract) {
+        let asset = AssetInfo::Cw20 { contract: contract.to_string() };
+        let amount = take(storage, &USER_PENALTIES, owner, contract, has_deposit)?;
+        UNCLAIMED_PENALTIES.update(storage, contract, |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default().checked_sub(amount)?)
+        })?;
+        claims.push((asset, amount));
+    }
+    Ok(claims)
+}
+
+fn take(
+    storage: &mut dyn Storage,
+    rewards: &Map<(&Addr, &Addr), UserReward>,
+    owner: &Addr,
+    contract: &Addr,
+    keep: bool,
+) -> StdResult<Uint128> {
+    let mut reward = rewards.may_load(storage, (owner, contract))?.unwrap_or_default();
+    let amount = reward.pending;
+    reward.pending = Uint128::zero();
+    if keep {
+        rewards.save(storage, (owner, contract), &reward)?;
+    } else {
+        rewards.remove(storage, (owner, contract));
+    }
+    Ok(amount)
+}
+
+/// What an owner could claim at `block` for a cw20, as (asset, amount) pairs, without changing
+/// storage.
+pub fn pending(storage: &dyn Storage, block: &BlockInfo, owner: &Addr, contract: &Addr) -> StdResult<Vec<(AssetInfo, Uint128)>> {
+    let staked = staked(storage, owner, contract)?;
+    let mut pending = vec![];
+    if let Some(mut pool) = REWARD_POOLS.may_load(storage, contract)? {
+        let total_staked = total_weight(storage, contract)?;
+        accrue(&mut pool, block.time, total_staked)?;
+        let reward = USER_REWARDS.may_load(storage, (owner, contract))?.unwrap_or_default();
+        pending.push((pool.reward, earned(&reward, pool.reward_per_token, staked)?));
+    }
+    if let Some(index) = PENALTY_INDEX.may_load(storage, contract)? {
+        let reward = USER_PENALTIES.may_load(storage, (owner, contract))?.unwrap_or_default();
+        pending.push((AssetInfo::Cw20 { contract: contract.to_string() }, earned(&reward, index, staked)?));
+    }
+    Ok(pending)
+}
@@ -1,28 +1,350 @@
-use cw20::Expiration;
+use cw_utils::{Duration, Expiration};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Uint128, Addr, Coin, BlockInfo};
-use cw_storage_plus::Map;
+use cosmwasm_std::{Uint128, Addr, BlockInfo, Decimal, Decimal256, Empty, StdResult, Storage, Timestamp};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
+use crate::msg::AssetInfo;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub admin: Addr,
+    /// proposed admin, becomes admin once it accepts
+    pub pending_admin: Option<Addr>,
+    /// default lock applied to every cw20 deposit
+    pub lock_duration: Duration,
+    /// locked cw20 can only be withdrawn early when set
+    pub early_withdraw: Option<EarlyWithdrawPolicy>,
+    /// when set, cw20 can only leave through an unbond followed by a claim once it has passed
+    pub unbonding_period: Option<Duration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EarlyWithdrawPolicy {
+    /// penalty right after the deposit, decaying linearly to zero at unlock
+    pub max_penalty: Decimal,
+    pub destination: PenaltyDestination,
+}
+
+/// Where the penalties of early withdraws go.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyDestination {
+    Treasury { address: Addr },
+    /// shared by the other depositors of the cw20, in proportion to their deposit
+    Stakers {},
+}
+
+/// Block at which a deposit or withdraw happened.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct Cw20Deposits {
-    pub count: i32,
-    pub owner: String,
-    pub contract:String,
-    pub amount:Uint128,
-    pub stake_time:Expiration
+pub struct BlockStamp {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+impl From<&BlockInfo> for BlockStamp {
+    fn from(block: &BlockInfo) -> Self {
+        BlockStamp { height: block.height, time: block.time }
+    }
 }
 
+/// Deposit of an owner in one asset. Records are removed once their amount reaches zero, so the
+/// counters and blocks cover the time since the balance was last empty.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct Deposits {
-    pub count: i32,
+pub struct Balance {
     pub owner: Addr,
-    pub coins: Coin
+    pub asset: AssetInfo,
+    pub amount: Uint128,
+    pub deposit_count: u64,
+    pub withdraw_count: u64,
+    pub last_deposit: Option<BlockStamp>,
+    pub last_withdraw: Option<BlockStamp>,
+}
+
+/// A single cw20 deposit, locked independently of the other lots of the owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20Lot {
+    pub id: u64,
+    pub amount: Uint128,
+    pub deposited_at: BlockStamp,
+    pub unlocks_at: Expiration,
+    /// tier picked at deposit, `None` for the default lock
+    pub lock_tier: Option<String>,
+    /// amount counted for rewards, the amount times the multiplier of the tier until it matures
+    pub weight: Uint128,
+    /// given by the depositor, kept when the lot is transferred
+    pub memo: Option<String>,
+}
+
+/// A single deposit of one cw1155 token id, locked like a cw20 lot. Cw1155 deposits earn no
+/// rewards, so the tier only sets the lock.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw1155Lot {
+    pub id: u64,
+    pub amount: Uint128,
+    pub deposited_at: BlockStamp,
+    pub unlocks_at: Expiration,
+    pub lock_tier: Option<String>,
+    pub memo: Option<String>,
+}
+
+/// Lock a depositor can pick instead of the default one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LockTier {
+    pub duration: Duration,
+    /// weight of the deposited tokens in the rewards, one for the default lock
+    pub reward_multiplier: Decimal,
+}
+
+/// Operations that the admin can halt independently of each other.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    NativeDeposit,
+    NativeWithdraw,
+    Cw20Deposit,
+    Cw20Withdraw,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::NativeDeposit => "native_deposit",
+            Operation::NativeWithdraw => "native_withdraw",
+            Operation::Cw20Deposit => "cw20_deposit",
+            Operation::Cw20Withdraw => "cw20_withdraw",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct PauseState {
+    pub native_deposit: bool,
+    pub native_withdraw: bool,
+    pub cw20_deposit: bool,
+    pub cw20_withdraw: bool,
+}
+
+impl PauseState {
+    pub fn is_paused(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::NativeDeposit => self.native_deposit,
+            Operation::NativeWithdraw => self.native_withdraw,
+            Operation::Cw20Deposit => self.cw20_deposit,
+            Operation::Cw20Withdraw => self.cw20_withdraw,
+        }
+    }
+}
+
+/// Bounds on a single cw20 deposit, `None` means unbounded.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct TokenLimits {
+    pub min_deposit: Option<Uint128>,
+    pub max_deposit: Option<Uint128>,
+}
+
+/// Bounds on native deposits of a denom, `None` means unbounded.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct DenomLimits {
+    /// smallest amount accepted in a single deposit
+    pub min_deposit: Option<Uint128>,
+    /// largest balance a single user can hold
+    pub max_user_balance: Option<Uint128>,
+    /// largest amount the contract holds across all users
+    pub global_cap: Option<Uint128>,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const PAUSED: Item<PauseState> = Item::new("paused");
+
+//key is cw20 contract address, only listed contracts can be deposited
+pub const ALLOWED_CW20S: Map<&Addr, TokenLimits> = Map::new("allowedcw20s");
+
+//key is cw1155 contract address, only listed contracts can be deposited
+pub const ALLOWED_CW1155S: Map<&Addr, Empty> = Map::new("allowedcw1155s");
+
+//key is denom, only listed denoms can be deposited
+pub const ALLOWED_DENOMS: Map<&str, DenomLimits> = Map::new("alloweddenoms");
+
+/// Where a native denom comes from, recorded when the denom is allowed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DenomKind {
+    /// issued by the chain itself
+    Native {},
+    /// received over ibc, `hash` is the hash of the trace of the denom
+    Ibc { hash: String },
+    /// created with the token factory module
+    TokenFactory { creator: String, subdenom: String },
+}
+
+/// Origin of an ibc denom, as given by the admin.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcTrace {
+    /// ports and channels the denom went through, e.g. `transfer/channel-0`
+    pub path: String,
+    /// denom on the origin chain, e.g. `uatom`
+    pub base_denom: String,
+}
+
+//key is denom, kind of every denom that has been allowed
+pub const DENOM_KINDS: Map<&str, DenomKind> = Map::new("denomkinds");
+
+//key is ibc denom, set by the admin
+pub const IBC_TRACES: Map<&str, IbcTrace> = Map::new("ibctraces");
+
+//key is denom, sum of the deposits of every user
+pub const DENOM_TOTALS: Map<&str, Uint128> = Map::new("denomtotals");
+
+//key is cw20 contract address, sum of the deposits of every user
+pub const CW20_TOTALS: Map<&Addr, Uint128> = Map::new("cw20totals");
+
+//key is cw20 contract address, sum of the unbonding claims of every user not paid out yet
+pub const CW20_UNBONDING_TOTALS: Map<&Addr, Uint128> = Map::new("cw20unbondingtotals");
+
+//key is address, every address that has deposited
+pub const DEPOSITORS: Map<&Addr, Empty> = Map::new("depositors");
+
+//key is tier name, locks depositors can choose from
+pub const LOCK_TIERS: Map<&str, LockTier> = Map::new("locktiers");
+
+//key is cw20 contract address, overrides the default lock duration
+pub const CW20_LOCK_DURATIONS: Map<&Addr, Duration> = Map::new("cw20lockdurations");
+
+pub struct BalanceIndexes<'a> {
+    // holders of an asset by asset key, ordered by amount
+    pub asset: MultiIndex<'a, (String, u128), Balance, (Addr, String, String)>,
 }
 
-//key is address, denom
-pub const DEPOSITS: Map<(&str, &str), Deposits> = Map::new("deposits");
+impl<'a> IndexList<Balance> for BalanceIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Balance>> + '_> {
+        let v: Vec<&dyn Index<Balance>> = vec![&self.asset];
+        Box::new(v.into_iter())
+    }
+}
+
+//key is address, asset kind, denom or cw20 contract address. See `balance_key`.
+pub fn balances<'a>() -> IndexedMap<'a, (&'a Addr, &'a str, &'a str), Balance, BalanceIndexes<'a>> {
+    let indexes = BalanceIndexes {
+        asset: MultiIndex::new(
+            |_pk, b| (asset_key(&b.asset), b.amount.u128()),
+            "balances",
+            "balances__asset",
+        ),
+    };
+    IndexedMap::new("balances", indexes)
+}
 
-//key is address, cw20 contract address
-pub const CW20_DEPOSITS: Map<(&str, &str), Cw20Deposits> = Map::new("cw20deposits");
+pub const NATIVE_KIND: &str = "native";
+pub const CW20_KIND: &str = "cw20";
+
+/// Kind and id of a validated asset in the balances, so the balances of one kind can be ranged
+/// over by denom or address.
+pub fn balance_key(asset: &AssetInfo) -> (&'static str, &str) {
+    match asset {
+        AssetInfo::Native { denom } => (NATIVE_KIND, denom),
+        AssetInfo::Cw20 { contract } => (CW20_KIND, contract),
+    }
+}
+
+/// Amount a spender can still withdraw from the deposit of an owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DepositAllowance {
+    pub asset: AssetInfo,
+    pub amount: Uint128,
+    pub expires: Expiration,
+}
+
+//key is owner, spender, asset key
+pub const ALLOWANCES: Map<(&Addr, &Addr, &str), DepositAllowance> = Map::new("allowances");
+
+/// Storage key of a validated asset. The kind comes first, so a denom containing `/` cannot
+/// collide with a cw20 address.
+pub fn asset_key(asset: &AssetInfo) -> String {
+    let (kind, id) = balance_key(asset);
+    format!("{}/{}", kind, id)
+}
+
+/// Rewards paid to the depositors of a cw20, in proportion to their deposit.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardPool {
+    pub reward: AssetInfo,
+    pub emission_per_second: Uint128,
+    /// funded rewards that have not been emitted yet
+    pub undistributed: Uint128,
+    /// rewards emitted per deposited token since the pool was created
+    pub reward_per_token: Decimal256,
+    pub last_update: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct UserReward {
+    /// `reward_per_token` of the pool when the owner was last settled
+    pub reward_per_token_paid: Decimal256,
+    /// settled rewards that have not been claimed
+    pub pending: Uint128,
+}
+
+//key is the deposited cw20 contract address
+pub const REWARD_POOLS: Map<&Addr, RewardPool> = Map::new("rewardpools");
+
+//key is owner, cw20 contract address. Sum of the weights of the lots of the owner
+pub const CW20_WEIGHTS: Map<(&Addr, &Addr), Uint128> = Map::new("cw20weights");
+
+//key is cw20 contract address, sum of the weights of every user
+pub const CW20_WEIGHTED_TOTALS: Map<&Addr, Uint128> = Map::new("cw20weightedtotals");
+
+//key is owner, deposited cw20 contract address
+pub const USER_REWARDS: Map<(&Addr, &Addr), UserReward> = Map::new("userrewards");
+
+//penalties shared with the depositors of a cw20 per deposited token, key is the cw20 contract address
+pub const PENALTY_INDEX: Map<&Addr, Decimal256> = Map::new("penaltyindex");
+
+//shares of the penalties, paid in the deposited cw20. Key is owner, cw20 contract address
+pub const USER_PENALTIES: Map<(&Addr, &Addr), UserReward> = Map::new("userpenalties");
+
+//key is cw20 contract address, penalties shared with the depositors and not claimed yet,
+//rounding leftovers included
+pub const UNCLAIMED_PENALTIES: Map<&Addr, Uint128> = Map::new("unclaimedpenalties");
+
+/// Unbonded cw20 waiting for the unbonding period to pass.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20Claim {
+    pub id: u64,
+    pub contract: Addr,
+    pub amount: Uint128,
+    pub release_at: Expiration,
+}
+
+//key is owner, claim id. Ids are increasing so claims range in the order they were made.
+pub const CW20_CLAIMS: Map<(&Addr, u64), Cw20Claim> = Map::new("cw20unbondings");
+
+//key is address, cw20 contract address, lot id. Ids are increasing so lots range oldest first.
+pub const CW20_LOTS: Map<(&Addr, &Addr, u64), Cw20Lot> = Map::new("cw20lots");
+
+//key is address, (cw1155 contract address, token id), lot id. Storage keys go up to three
+//parts, so the contract and the token id are nested.
+pub const CW1155_LOTS: Map<(&Addr, (&Addr, &str), u64), Cw1155Lot> = Map::new("cw1155lots");
+
+//key is address, cw1155 contract address, token id. Removed once it reaches zero.
+pub const CW1155_BALANCES: Map<(&Addr, &Addr, &str), Uint128> = Map::new("cw1155balances");
+
+//last lot id handed out
+pub const LOT_SEQ: Item<u64> = Item::new("lotseq");
+
+pub fn next_lot_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let id = LOT_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    LOT_SEQ.save(storage, &id)?;
+    Ok(id)
+}
+
+//last claim id handed out
+pub const CLAIM_SEQ: Item<u64> = Item::new("claimseq");
+
+pub fn next_claim_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let id = CLAIM_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    CLAIM_SEQ.save(storage, &id)?;
+    Ok(id)
+}